@@ -22,15 +22,74 @@ global.write_file = (file, content) => fs.writeFileSync(file, content);
 program
   .option("-i, --input <input>", "Input directory", "./shaders")
   .option("-o, --output <output>", "Output directory", "./output")
-  .option("-f, --file <file>", "File to process")
-  .option("-w, --watch", "Watch for changes", false);
+  .option("-f, --file <file>", "File to process (defaults to every shader of the input folder)")
+  .option("-w, --watch", "Watch for changes", false)
+  .option("-I, --include <folders...>", "Folders searched for #include <...>")
+  .option("-D, --define <defines...>", "Defines inserted after #version, as NAME or NAME=VALUE")
+  .option("-c, --config <config>", "Config file (defaults to the closest glsl-types.toml or glsl-types.json)")
+  .option("--format <formats...>", "Output formats (typescript, rust, cpp, json, template)")
+  .option("--template <template>", "Template rendered by the template format")
+  .option("-l, --language <language>", "Output format, same as --format with a single one")
+  .option("--emit <emit>", "Files written by the TypeScript format (ts, or js for .js and .d.ts)")
+  .option("--module <module>", "Module format of the .js files (esm, cjs)")
+  .option("--as-const", "Type the exported objects with the literal types of their values")
+  .option("--source-mode <mode>", "How the source is embedded (template, json, raw for a .glsl file)")
+  .option("--sort-members", "Sort the uniforms, inputs, outputs and constants by name")
+  .option("--vertex-layout", "Generate an interleaved vertex layout for vertex shaders")
+  .option("-n, --naming <naming>", "Naming convention of the exports (pascal, camel)")
+  .option("--no-stage-suffix", "Do not add the extension of a shader to its outputs and exports")
+  .option("-t, --target <target>", "Target profile (webgl1, webgl2, opengl)")
+  .option("--tree-shake", "Remove the code that main does not use")
+  .option("--keep-uniforms", "Keep unused uniforms when removing unused code")
+  .option("--minify", "Minify the shader source")
+  .option("--preprocess", "Evaluate the preprocessor conditionals and expand the macros")
+  .option("--mangle", "Rename uniforms, inputs and outputs to hashed names in the shader source");
 
 program.parse();
 
-const SHADER_EXTENSIONS = [".vert", ".frag", ".vs", ".fs"];
-
 const options = program.opts();
 
+// Settings forwarded to the Rust side, where they are merged with the config file
+const cliOptions = JSON.stringify({
+  config: options.config,
+  include_paths: options.include,
+  format: options.format,
+  language: options.language,
+  template: options.template,
+  naming: options.naming,
+  // Commander defaults stageSuffix to true, which would override the config file
+  stage_suffix: options.stageSuffix === false ? false : undefined,
+  emit: options.emit,
+  module: options.module,
+  as_const: options.asConst,
+  source_mode: options.sourceMode,
+  sort_members: options.sortMembers,
+  vertex_layout: options.vertexLayout,
+  target: options.target,
+  tree_shake: options.treeShake,
+  keep_uniforms: options.keepUniforms,
+  minify: options.minify,
+  mangle: options.mangle,
+  preprocess: options.preprocess,
+});
+
+// A bad option or config file would otherwise make every file look like it is not a shader
+if (!glslTypes.check_options(cliOptions)) {
+  process.exit(1);
+}
+
+// Defines given with -D, e.g. -D USE_FOG MAX_LIGHTS=8
+const defines: string[] = options.define ?? [];
+
+// The shader files of the input folder, sorted so that the index does not depend on the file system
+function shaderFiles(): string[] {
+  return fs
+    .readdirSync(options.input, { recursive: true })
+    .map((file) => path.join(options.input, file.toString()))
+    .filter((file) => glslTypes.is_shader_file(file, cliOptions))
+    .sort();
+}
+
 if (options.watch) {
   process.stdout.write(chalk.green("Watching for changes\n"));
   fs.watch(options.input, { recursive: true }, (eventType, filename) => {
@@ -38,30 +97,41 @@ if (options.watch) {
     if (!filename) return;
     filename = path.resolve(options.input, filename);
 
-    if (SHADER_EXTENSIONS.includes(path.extname(filename))) {
-      const start = performance.now();
-      glslTypes.start_cli(filename, options.input, options.output);
-      const end = performance.now();
+    if (glslTypes.is_shader_file(filename, cliOptions)) {
+      // A removed shader only changes the index
+      const files = shaderFiles();
+      if (!glslTypes.check_outputs(files, options.input, cliOptions)) return;
+
+      if (fs.existsSync(filename)) {
+        const start = performance.now();
+        glslTypes.start_cli(filename, options.input, options.output, cliOptions, defines);
+        const end = performance.now();
 
-      process.stdout.write(chalk.green("[INFO]\t"));
-      process.stdout.write(
-        `File processed ${chalk.blue(path.relative(options.input, filename))}`
-      );
-      process.stdout.write(chalk.gray(` (${(end - start).toFixed(2)}ms)\n`));
+        process.stdout.write(chalk.green("[INFO]\t"));
+        process.stdout.write(
+          `File processed ${chalk.blue(path.relative(options.input, filename))}`
+        );
+        process.stdout.write(chalk.gray(` (${(end - start).toFixed(2)}ms)\n`));
+      }
+
+      glslTypes.write_index(files, options.input, options.output, cliOptions);
     }
   });
-} else {
-  if (!options.file) {
-    console.error("Please provide a file to process");
+} else if (options.file) {
+  if (!fs.existsSync(options.file)) {
+    console.error(`File ${options.file} does not exist`);
     process.exit(1);
   }
 
-  if (!fs.existsSync(options.file)) {
-    console.error(`File ${options.file} does not exist`);
+  glslTypes.start_cli(options.file, options.input, options.output, cliOptions, defines);
+} else {
+  // Without a file, every shader of the input folder is processed
+  if (!fs.existsSync(options.input)) {
+    console.error(`Folder ${options.input} does not exist`);
     process.exit(1);
   }
 
-  glslTypes.start_cli(options.file, options.input, options.output);
+  glslTypes.start_batch(shaderFiles(), options.input, options.output, cliOptions, defines);
 }
 
 declare global {
@@ -1,9 +1,50 @@
-use crate::generator::type_script;
+use crate::config::{self, CliOptions, Config, DefineValue, Settings};
+use crate::generator::common::{self, ProcessedShader, ShaderData, ShaderVariant};
+use crate::generator::index::{generate_index, IndexEntry};
+use crate::generator::naming;
+use crate::generator::{Generator, OutputFile, Reflection, Registry};
 use crate::import_resolver;
-use crate::{canonicalize, create_dir_all, file_exists};
-use std::path::PathBuf;
+use crate::import_resolver::import_resolver::{insert_defines, CombinedShader};
+use crate::preprocessor::preprocess::preprocess;
+use crate::preprocessor::variants::{permutations, variant_options};
+use crate::transform::mangle::mangle;
+use crate::transform::minify::minify;
+use crate::transform::tree_shake::tree_shake;
+use crate::utils::get_shader_type::{get_shader_type, ShaderType};
+use crate::utils::log::{print_level, Level};
+use crate::utils::path::to_forward_slashes;
+use crate::{canonicalize, create_dir_all, file_exists, log, log_with_color, logln, write_file};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
-pub fn generate(file_path: String, input_folder: String, output_folder: String) {
+pub fn generate(
+  file_path: String,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+  defines: Vec<String>,
+) {
+  generate_with_registry(
+    &Registry::default(),
+    file_path,
+    input_folder,
+    output_folder,
+    options,
+    defines,
+  );
+}
+
+/// Generates the outputs of a shader with the generators of `registry`, which can have other
+/// generators than the built-in ones.
+pub fn generate_with_registry(
+  registry: &Registry,
+  file_path: String,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+  defines: Vec<String>,
+) {
   if !file_exists(&input_folder) {
     create_dir_all(&input_folder);
   }
@@ -16,17 +57,409 @@ pub fn generate(file_path: String, input_folder: String, output_folder: String)
   let input_folder = PathBuf::from(input_folder);
   let output_folder = PathBuf::from(output_folder);
 
+  let mut options = match CliOptions::parse(&options) {
+    Ok(options) => options,
+    Err(error) => {
+      config::print_config_error(&error);
+      return;
+    }
+  };
+
+  if let Err(error) = config::apply_define_flags(&mut options.settings, &defines) {
+    config::print_config_error(&error);
+    return;
+  }
+
+  let config = match Config::load(options.config.as_ref()) {
+    Ok(config) => config,
+    Err(error) => {
+      config::print_config_error(&error);
+      return;
+    }
+  };
+
+  let settings = config.settings_for(&file_path, &options.settings);
+
+  let mut generators: Vec<&dyn Generator> = Vec::new();
+  for format in settings.formats() {
+    match registry.get(&format) {
+      Some(generator) => generators.push(generator),
+      None => {
+        print_level(Level::ERROR);
+        log("Unknown format ");
+        log_with_color(&format, "blue");
+        logln(&format!(
+          ", expected one of {}",
+          registry.names().join(", ")
+        ));
+        return;
+      }
+    }
+  }
+
+  let shader_type = match get_shader_type(&file_path, &settings.stages()) {
+    Some(shader_type) => shader_type,
+    None => {
+      print_level(Level::ERROR);
+      log("Could not determine the shader stage of ");
+      log_with_color(&to_forward_slashes(&file_path), "blue");
+      logln(". Add its extension to the stages in the config file.");
+      return;
+    }
+  };
+
   // Update the file path to be relative to the input folder
-  let input_folder_canon = PathBuf::from(canonicalize(&input_folder.to_str().unwrap()));
+  let input_folder_canon = PathBuf::from(canonicalize(input_folder.to_str().unwrap()));
   let input_folder_parent = &input_folder_canon.parent().unwrap().to_path_buf();
 
-  let combined_vertex = if let Some(output) =
-    import_resolver::import_resolver::try_resolve_imports(&file_path, input_folder_parent)
-  {
+  let combined = if let Some(output) = import_resolver::import_resolver::try_resolve_imports(
+    &file_path,
+    input_folder_parent,
+    settings.include_paths(),
+    // The defines are inserted by `process`, as every variant has its own
+    &[],
+  ) {
     output
   } else {
     return;
   };
 
-  type_script::generate_types_file(combined_vertex, &file_path, &output_folder);
+  let shader = match process(&combined, &file_path, &settings, &[], settings.preprocess()) {
+    Some(shader) => shader,
+    None => return,
+  };
+
+  let data = extract_shader_data(&shader.content, shader_type, &settings);
+  print_warnings(&data, &file_path, &settings);
+
+  // Every variant is preprocessed, with its defines on top of the configured ones
+  let options = variant_options(&settings.variants(), &combined.source);
+  let mut variants = Vec::new();
+  if !options.is_empty() {
+    let permutations = match permutations(&options) {
+      Ok(permutations) => permutations,
+      Err(error) => {
+        print_transform_error("Could not generate the variants of ", &file_path, error);
+        return;
+      }
+    };
+
+    for defines in permutations {
+      match process(&combined, &file_path, &settings, &defines, true) {
+        Some(shader) => variants.push(ShaderVariant {
+          defines,
+          data: extract_shader_data(&shader.content, shader_type, &settings),
+          shader,
+        }),
+        None => return,
+      }
+    }
+  }
+
+  let reflection = Reflection {
+    name: naming::output_name(&file_path, &settings),
+    export_name: naming::export_name(&file_path, &settings),
+    stage: shader_type,
+    shader,
+    data,
+    variants,
+  };
+
+  // The outputs mirror the folders of the input folder
+  let output_folder = output_folder.join(output_directory(&file_path, &input_folder));
+  for generator in generators {
+    match generator.generate(&reflection, &settings) {
+      Ok(files) => {
+        for file in files {
+          write_output_file(&output_folder, &file);
+        }
+      }
+      Err(error) => {
+        let message = format!("Could not generate the {} output of ", generator.name());
+        print_transform_error(&message, &file_path, error);
+      }
+    }
+  }
+}
+
+/// Generates the outputs of every shader of `files` and their index, unless the outputs of some
+/// of them would overwrite each other.
+pub fn generate_batch(
+  files: Vec<String>,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+  defines: Vec<String>,
+) {
+  if !check_outputs(&files, &input_folder, &options) {
+    return;
+  }
+
+  for file in &files {
+    generate(
+      file.clone(),
+      input_folder.clone(),
+      output_folder.clone(),
+      options.clone(),
+      defines.clone(),
+    );
+  }
+
+  write_index(files, input_folder, output_folder, options);
+}
+
+/// Checks that no two shaders of `files` write their outputs to the same path, e.g. `blur.vert`
+/// and `blur.frag` without stage suffixes. Every collision is printed.
+pub fn check_outputs(files: &[String], input_folder: &str, options: &str) -> bool {
+  let (options, config) = match load_config(options) {
+    Some(loaded) => loaded,
+    None => return false,
+  };
+
+  let mut outputs: BTreeMap<PathBuf, &String> = BTreeMap::new();
+  let mut exports: BTreeMap<(PathBuf, String), &String> = BTreeMap::new();
+  let mut is_valid = true;
+  for file in files {
+    let file_path = PathBuf::from(file);
+    let settings = config.settings_for(&file_path, &options.settings);
+    let path = output_path(&file_path, Path::new(input_folder), &settings);
+
+    // Shaders of a folder are exported next to each other in the index, e.g. `my-shader.vert`
+    // and `my_shader.vert` would both be `MyShaderVert`
+    let export = (
+      output_directory(&file_path, Path::new(input_folder)),
+      naming::export_name(&file_path, &settings),
+    );
+
+    if let Some(other) = outputs.get(&path) {
+      print_collision(
+        other,
+        file,
+        &format!("outputs would both be named {}", to_forward_slashes(&path)),
+      );
+      is_valid = false;
+    } else if let Some(other) = exports.get(&export) {
+      print_collision(
+        other,
+        file,
+        &format!("exports would both be named {}", export.1),
+      );
+      is_valid = false;
+    } else {
+      outputs.insert(path, file);
+      exports.insert(export, file);
+    }
+  }
+
+  is_valid
+}
+
+/// Prints that `file` collides with `existing`, a file checked before it.
+fn print_collision(existing: &str, file: &str, message: &str) {
+  print_level(Level::ERROR);
+  log("The ");
+  log_with_color(&to_forward_slashes(Path::new(existing)), "blue");
+  log(" and ");
+  log_with_color(&to_forward_slashes(Path::new(file)), "blue");
+  logln(&format!(" {}. Rename one of them.", message));
+}
+
+/// Writes the index of the TypeScript outputs of `files`, the shader files of the input folder.
+/// Shaders that are not generated as TypeScript are left out.
+pub fn write_index(
+  files: Vec<String>,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+) {
+  let (options, config) = match load_config(&options) {
+    Some(loaded) => loaded,
+    None => return,
+  };
+
+  let mut entries = Vec::new();
+  for file in &files {
+    let file_path = PathBuf::from(file);
+    let settings = config.settings_for(&file_path, &options.settings);
+    let is_typescript = settings
+      .formats()
+      .iter()
+      .any(|format| format == "typescript");
+    if !is_typescript || get_shader_type(&file_path, &settings.stages()).is_none() {
+      continue;
+    }
+
+    let module = to_forward_slashes(&output_path(
+      &file_path,
+      Path::new(&input_folder),
+      &settings,
+    ));
+    entries.push(IndexEntry {
+      module,
+      export: naming::export_name(&file_path, &settings),
+    });
+  }
+
+  // The index is not one of the shaders, so overrides do not apply to it
+  for file in generate_index(&entries, &config.project_settings(&options.settings)) {
+    write_output_file(Path::new(&output_folder), &file);
+  }
+}
+
+pub(crate) fn load_config(options: &str) -> Option<(CliOptions, Config)> {
+  let options = match CliOptions::parse(options) {
+    Ok(options) => options,
+    Err(error) => {
+      config::print_config_error(&error);
+      return None;
+    }
+  };
+
+  match Config::load(options.config.as_ref()) {
+    Ok(config) => Some((options, config)),
+    Err(error) => {
+      config::print_config_error(&error);
+      None
+    }
+  }
+}
+
+/// Gets the folder of the outputs of a shader, relative to the output folder. It is the folder
+/// of the shader relative to the input folder, or the output folder itself for shaders outside
+/// of the input folder.
+fn output_directory(file_path: &Path, input_folder: &Path) -> PathBuf {
+  let file_path = PathBuf::from(canonicalize(file_path.to_str().unwrap()));
+  let input_folder = PathBuf::from(canonicalize(input_folder.to_str().unwrap()));
+
+  match file_path
+    .parent()
+    .and_then(|parent| parent.strip_prefix(&input_folder).ok())
+  {
+    Some(directory) => directory.to_path_buf(),
+    None => PathBuf::new(),
+  }
+}
+
+/// Gets the path of the outputs of a shader relative to the output folder, without extension.
+fn output_path(file_path: &Path, input_folder: &Path, settings: &Settings) -> PathBuf {
+  output_directory(file_path, input_folder).join(naming::output_name(file_path, settings))
+}
+
+/// Extracts the interface of a shader, sorted by name if the settings ask for it.
+fn extract_shader_data(
+  content: &String,
+  shader_type: ShaderType,
+  settings: &Settings,
+) -> ShaderData {
+  let mut data = common::extract_shader_data(content, shader_type);
+  if settings.sort_members() {
+    data.sort_by_name();
+  }
+
+  data
+}
+
+fn write_output_file(output_folder: &Path, file: &OutputFile) {
+  let path = output_folder.join(&file.path);
+  if let Some(parent) = path.parent() {
+    if !file_exists(parent.to_str().unwrap()) {
+      create_dir_all(parent.to_str().unwrap());
+    }
+  }
+
+  write_file(path.to_str().unwrap(), &file.contents);
+}
+
+/// Warns about a shader that can fail on some devices of the target.
+fn print_warnings(data: &ShaderData, file_path: &Path, settings: &Settings) {
+  // The vertex shader has more attributes than the target guarantees
+  let target = settings.target();
+  if data.ins.len() > target.max_vertex_attributes() && data.shader_type == ShaderType::Vertex {
+    print_level(Level::WARN);
+    log("The vertex shader ");
+    log_with_color(&to_forward_slashes(file_path), "blue");
+    logln(&format!(
+      " has more than {} attributes ({}). This can cause issues in some devices.",
+      target.max_vertex_attributes(),
+      target.name()
+    ));
+  }
+}
+
+fn print_transform_error(message: &str, file_path: &Path, error: impl Display) {
+  print_level(Level::ERROR);
+  log(message);
+  log_with_color(&to_forward_slashes(file_path), "blue");
+  logln(&format!(": {}", error));
+}
+
+/// Runs the transforms enabled in the settings on a combined shader. The configured defines,
+/// with the ones of the variant on top, are inserted after the `#version` line.
+fn process(
+  combined: &CombinedShader,
+  file_path: &Path,
+  settings: &Settings,
+  variant: &[(String, DefineValue)],
+  should_preprocess: bool,
+) -> Option<ProcessedShader> {
+  let defines = settings.defines_with(variant);
+  let content = insert_defines(&combined.source, &defines);
+
+  // Reflection and the output only see the branches of the conditionals that are compiled
+  let content = if should_preprocess {
+    match preprocess(&content, settings.target()) {
+      Ok(output) => output,
+      Err(error) => {
+        print_transform_error("Could not preprocess ", file_path, error);
+        return None;
+      }
+    }
+  } else {
+    content
+  };
+
+  let content = if settings.tree_shake() {
+    match tree_shake(&content, settings.keep_uniforms()) {
+      Ok(output) => output,
+      Err(error) => {
+        print_transform_error("Could not remove the unused code of ", file_path, error);
+        return None;
+      }
+    }
+  } else {
+    content
+  };
+
+  // The types are generated from the source before renaming its interface
+  let (source, names) = if settings.mangle() {
+    match mangle(&content) {
+      Ok(output) => output,
+      Err(error) => {
+        print_transform_error("Could not mangle the names of ", file_path, error);
+        return None;
+      }
+    }
+  } else {
+    (content.clone(), BTreeMap::new())
+  };
+
+  let source = if settings.minify() {
+    match minify(&source) {
+      Ok(output) => output,
+      Err(error) => {
+        print_transform_error("Could not minify ", file_path, error);
+        return None;
+      }
+    }
+  } else {
+    source
+  };
+
+  Some(ProcessedShader {
+    defines,
+    includes: combined.includes.clone(),
+    content,
+    source,
+    names,
+  })
 }
@@ -0,0 +1,655 @@
+use crate::utils::get_shader_type::ShaderType;
+use crate::utils::log::{print_level, Level};
+use crate::utils::path::to_forward_slashes;
+use crate::{canonicalize, file_exists, log, log_with_color, logln, read_file};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILE_NAMES: [&str; 2] = ["glsl-types.toml", "glsl-types.json"];
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NamingConvention {
+  #[default]
+  Pascal,
+  Camel,
+}
+
+/// The files written by the TypeScript generator: a `.ts` module, or a `.js` module with its
+/// `.d.ts` declarations.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Emit {
+  #[default]
+  Ts,
+  Js,
+}
+
+/// How the TypeScript generator embeds the source of a shader: in a template literal, in a JSON
+/// string, or in a `.glsl` file imported with `?raw`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceMode {
+  #[default]
+  Template,
+  Json,
+  Raw,
+}
+
+/// The module format of the `.js` files.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleFormat {
+  #[default]
+  Esm,
+  Cjs,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+  WebGL1,
+  #[default]
+  WebGL2,
+  OpenGL,
+}
+
+impl Target {
+  /// The minimum number of vertex attributes every implementation of the target supports.
+  pub fn max_vertex_attributes(&self) -> usize {
+    match self {
+      Target::WebGL1 => 8,
+      Target::WebGL2 => 16,
+      Target::OpenGL => 16,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Target::WebGL1 => "webgl1",
+      Target::WebGL2 => "webgl2",
+      Target::OpenGL => "opengl",
+    }
+  }
+}
+
+/// The value of a define. `true` defines the macro as `1` and `false` leaves it undefined.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum DefineValue {
+  Bool(bool),
+  Integer(i64),
+  Float(f64),
+  Text(String),
+}
+
+impl DefineValue {
+  /// Parses the value of a define written in a shader or on the command line.
+  pub fn parse(value: &str) -> DefineValue {
+    match value {
+      "true" => DefineValue::Bool(true),
+      "false" => DefineValue::Bool(false),
+      _ => value
+        .parse::<i64>()
+        .map(DefineValue::Integer)
+        .or_else(|_| value.parse::<f64>().map(DefineValue::Float))
+        .unwrap_or_else(|_| DefineValue::Text(value.to_string())),
+    }
+  }
+
+  pub fn to_macro_value(&self) -> Option<String> {
+    match self {
+      DefineValue::Bool(true) => Some(String::from("1")),
+      DefineValue::Bool(false) => None,
+      DefineValue::Integer(value) => Some(value.to_string()),
+      DefineValue::Float(value) => Some(format!("{:?}", value)),
+      DefineValue::Text(value) => Some(value.clone()),
+    }
+  }
+}
+
+/// Settings that can be set in the config file, in an override or through CLI flags.
+///
+/// Every field is optional so that the different sources can be layered on top of each other.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+  pub include_paths: Option<Vec<PathBuf>>,
+  pub stages: Option<BTreeMap<String, ShaderType>>,
+  /// The output format, kept for the config files written before `format`.
+  pub language: Option<String>,
+  /// The names of the generators to run, as a single name or a list.
+  #[serde(deserialize_with = "one_or_many")]
+  pub format: Option<Vec<String>>,
+  /// The template rendered by the `template` format.
+  pub template: Option<PathBuf>,
+  pub naming: Option<NamingConvention>,
+  /// Whether the extension of a shader is a suffix of its outputs and exports, e.g. `BlurFrag`
+  /// for `blur.frag`, so that `blur.vert` and `blur.frag` do not overwrite each other.
+  pub stage_suffix: Option<bool>,
+  /// Words added before and after the names of the exports, e.g. `Shader` for `BlurFragShader`.
+  pub export_prefix: Option<String>,
+  pub export_suffix: Option<String>,
+  pub emit: Option<Emit>,
+  pub module: Option<ModuleFormat>,
+  pub as_const: Option<bool>,
+  pub source_mode: Option<SourceMode>,
+  /// Whether the uniforms, inputs, outputs and constants are sorted by name rather than in the
+  /// order they are declared in.
+  pub sort_members: Option<bool>,
+  /// Whether the TypeScript output of a vertex shader has helpers to lay out its attributes in
+  /// an interleaved buffer.
+  pub vertex_layout: Option<bool>,
+  pub target: Option<Target>,
+  pub tree_shake: Option<bool>,
+  pub keep_uniforms: Option<bool>,
+  pub minify: Option<bool>,
+  pub mangle: Option<bool>,
+  pub preprocess: Option<bool>,
+  pub defines: Option<BTreeMap<String, DefineValue>>,
+  pub variants: Option<BTreeMap<String, Vec<DefineValue>>>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+  }
+
+  Ok(
+    Option::<OneOrMany>::deserialize(deserializer)?.map(|value| match value {
+      OneOrMany::One(value) => vec![value],
+      OneOrMany::Many(values) => values,
+    }),
+  )
+}
+
+impl Settings {
+  /// Layers `other` on top of `self`. Scalar values in `other` win, lists and maps are combined.
+  pub fn merge(mut self, other: Settings) -> Settings {
+    if let Some(include_paths) = other.include_paths {
+      let mut merged = include_paths;
+      merged.extend(self.include_paths.take().unwrap_or_default());
+      self.include_paths = Some(merged);
+    }
+
+    if let Some(stages) = other.stages {
+      self.stages.get_or_insert_with(BTreeMap::new).extend(stages);
+    }
+
+    if let Some(defines) = other.defines {
+      self
+        .defines
+        .get_or_insert_with(BTreeMap::new)
+        .extend(defines);
+    }
+
+    if let Some(variants) = other.variants {
+      self
+        .variants
+        .get_or_insert_with(BTreeMap::new)
+        .extend(variants);
+    }
+
+    // Both select the generators, so the format of the config file is replaced by `--language`
+    if other.format.is_some() || other.language.is_some() {
+      self.format = other.format;
+      self.language = other.language;
+    }
+
+    self.naming = other.naming.or(self.naming);
+    self.stage_suffix = other.stage_suffix.or(self.stage_suffix);
+    self.export_prefix = other.export_prefix.or(self.export_prefix);
+    self.export_suffix = other.export_suffix.or(self.export_suffix);
+    self.emit = other.emit.or(self.emit);
+    self.module = other.module.or(self.module);
+    self.as_const = other.as_const.or(self.as_const);
+    self.source_mode = other.source_mode.or(self.source_mode);
+    self.sort_members = other.sort_members.or(self.sort_members);
+    self.vertex_layout = other.vertex_layout.or(self.vertex_layout);
+    self.target = other.target.or(self.target);
+    self.tree_shake = other.tree_shake.or(self.tree_shake);
+    self.keep_uniforms = other.keep_uniforms.or(self.keep_uniforms);
+    self.minify = other.minify.or(self.minify);
+    self.mangle = other.mangle.or(self.mangle);
+    self.preprocess = other.preprocess.or(self.preprocess);
+    self.template = other.template.or(self.template.take());
+    self
+  }
+
+  /// Makes every relative path in the settings relative to `root` instead.
+  fn resolve_paths(mut self, root: &Path) -> Settings {
+    if let Some(include_paths) = &mut self.include_paths {
+      for path in include_paths.iter_mut() {
+        *path = PathBuf::from(canonicalize(root.join(&path).to_str().unwrap()));
+      }
+    }
+
+    if let Some(template) = &mut self.template {
+      *template = PathBuf::from(canonicalize(root.join(&template).to_str().unwrap()));
+    }
+
+    self
+  }
+
+  pub fn include_paths(&self) -> &[PathBuf] {
+    self.include_paths.as_deref().unwrap_or_default()
+  }
+
+  pub fn template(&self) -> Option<&Path> {
+    self.template.as_deref()
+  }
+
+  pub fn stages(&self) -> BTreeMap<String, ShaderType> {
+    self.stages.clone().unwrap_or_default()
+  }
+
+  /// Gets the names of the generators to run, TypeScript by default.
+  pub fn formats(&self) -> Vec<String> {
+    match (&self.format, &self.language) {
+      (Some(format), _) => format.clone(),
+      (None, Some(language)) => vec![language.clone()],
+      (None, None) => vec!["typescript".to_string()],
+    }
+  }
+
+  pub fn naming(&self) -> NamingConvention {
+    self.naming.unwrap_or_default()
+  }
+
+  pub fn stage_suffix(&self) -> bool {
+    self.stage_suffix.unwrap_or(true)
+  }
+
+  pub fn export_prefix(&self) -> &str {
+    self.export_prefix.as_deref().unwrap_or("")
+  }
+
+  pub fn export_suffix(&self) -> &str {
+    self.export_suffix.as_deref().unwrap_or("")
+  }
+
+  pub fn target(&self) -> Target {
+    self.target.unwrap_or_default()
+  }
+
+  pub fn tree_shake(&self) -> bool {
+    self.tree_shake.unwrap_or(false)
+  }
+
+  pub fn keep_uniforms(&self) -> bool {
+    self.keep_uniforms.unwrap_or(false)
+  }
+
+  pub fn emit(&self) -> Emit {
+    self.emit.unwrap_or_default()
+  }
+
+  pub fn module(&self) -> ModuleFormat {
+    self.module.unwrap_or_default()
+  }
+
+  pub fn as_const(&self) -> bool {
+    self.as_const.unwrap_or(false)
+  }
+
+  pub fn source_mode(&self) -> SourceMode {
+    self.source_mode.unwrap_or_default()
+  }
+
+  pub fn sort_members(&self) -> bool {
+    self.sort_members.unwrap_or(false)
+  }
+
+  pub fn vertex_layout(&self) -> bool {
+    self.vertex_layout.unwrap_or(false)
+  }
+
+  pub fn minify(&self) -> bool {
+    self.minify.unwrap_or(false)
+  }
+
+  pub fn mangle(&self) -> bool {
+    self.mangle.unwrap_or(false)
+  }
+
+  pub fn preprocess(&self) -> bool {
+    self.preprocess.unwrap_or(false)
+  }
+
+  /// Gets every define, without the ones set to `false`.
+  pub fn defines(&self) -> Vec<(String, DefineValue)> {
+    self.defines_with(&[])
+  }
+
+  /// Gets every define once `extra` is applied on top of the configured ones, without the ones
+  /// set to `false`.
+  pub fn defines_with(&self, extra: &[(String, DefineValue)]) -> Vec<(String, DefineValue)> {
+    let mut defines = self.defines.clone().unwrap_or_default();
+    defines.extend(extra.iter().cloned());
+
+    defines
+      .into_iter()
+      .filter(|(_, value)| *value != DefineValue::Bool(false))
+      .collect()
+  }
+
+  pub fn variants(&self) -> BTreeMap<String, Vec<DefineValue>> {
+    self.variants.clone().unwrap_or_default()
+  }
+}
+
+/// Options passed by the JS side of the CLI, serialized as JSON.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CliOptions {
+  pub config: Option<PathBuf>,
+  #[serde(flatten)]
+  pub settings: Settings,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, IgnoredAny>,
+}
+
+// `deny_unknown_fields` does not work with `flatten`, so the keys that no field takes are
+// collected in `unknown` instead, and reported as errors.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+  #[serde(flatten)]
+  settings: Settings,
+  overrides: Vec<OverrideEntry>,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OverrideEntry {
+  files: String,
+  #[serde(flatten)]
+  settings: Settings,
+  #[serde(flatten)]
+  unknown: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Debug)]
+struct Override {
+  matcher: GlobMatcher,
+  settings: Settings,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+  InvalidOptions(String),
+  FileNotFound(PathBuf),
+  CouldNotParseFile(PathBuf, String),
+  InvalidGlob(PathBuf, String, String),
+  UnknownKeys(PathBuf, Vec<String>),
+  InvalidDefine(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+  /// The folder the config file lives in. Globs in overrides are relative to it.
+  pub root: Option<PathBuf>,
+  settings: Settings,
+  overrides: Vec<Override>,
+}
+
+impl Config {
+  /// Loads the config file at `path`, or the first one found walking up from the working
+  /// directory. Having no config file at all is not an error.
+  pub fn load(path: Option<&PathBuf>) -> Result<Config, ConfigError> {
+    let path = match path {
+      Some(path) => {
+        let path = PathBuf::from(canonicalize(path.to_str().unwrap()));
+        if !file_exists(path.to_str().unwrap()) {
+          return Err(ConfigError::FileNotFound(path));
+        }
+        path
+      }
+      None => match Self::discover(&PathBuf::from(canonicalize("."))) {
+        Some(path) => path,
+        None => return Ok(Config::default()),
+      },
+    };
+
+    Self::parse(&path)
+  }
+
+  fn discover(working_directory: &Path) -> Option<PathBuf> {
+    for directory in working_directory.ancestors() {
+      for name in CONFIG_FILE_NAMES {
+        let candidate = directory.join(name);
+        if file_exists(candidate.to_str().unwrap()) {
+          return Some(candidate);
+        }
+      }
+    }
+
+    None
+  }
+
+  fn parse(path: &Path) -> Result<Config, ConfigError> {
+    let contents = read_file(path.to_str().unwrap().to_string());
+
+    let file: ConfigFile = if path
+      .extension()
+      .is_some_and(|extension| extension == "json")
+    {
+      serde_json::from_str(&contents).map_err(|e| e.to_string())
+    } else {
+      toml::from_str(&contents).map_err(|e| e.to_string())
+    }
+    .map_err(|e| ConfigError::CouldNotParseFile(path.to_path_buf(), e))?;
+
+    let mut unknown_keys = file.unknown.keys().cloned().collect::<Vec<String>>();
+    for (i, entry) in file.overrides.iter().enumerate() {
+      unknown_keys.extend(
+        entry
+          .unknown
+          .keys()
+          .map(|key| format!("overrides[{}].{}", i, key)),
+      );
+    }
+
+    if !unknown_keys.is_empty() {
+      return Err(ConfigError::UnknownKeys(path.to_path_buf(), unknown_keys));
+    }
+
+    let root = path.parent().unwrap().to_path_buf();
+    let mut overrides = Vec::new();
+
+    for entry in file.overrides {
+      let matcher = GlobBuilder::new(&entry.files)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| {
+          ConfigError::InvalidGlob(path.to_path_buf(), entry.files.clone(), e.to_string())
+        })?
+        .compile_matcher();
+
+      overrides.push(Override {
+        matcher,
+        settings: entry.settings.resolve_paths(&root),
+      });
+    }
+
+    Ok(Config {
+      settings: file.settings.resolve_paths(&root),
+      root: Some(root),
+      overrides,
+    })
+  }
+
+  /// Computes the settings of the whole project: the top level settings, then the CLI flags.
+  pub fn project_settings(&self, cli_settings: &Settings) -> Settings {
+    let cli_settings = cli_settings
+      .clone()
+      .resolve_paths(&PathBuf::from(canonicalize(".")));
+    self.settings.clone().merge(cli_settings)
+  }
+
+  /// Computes the settings for a single shader: the top level settings, then every override
+  /// whose glob matches the file (in declaration order), then the CLI flags.
+  pub fn settings_for(&self, file_path: &Path, cli_settings: &Settings) -> Settings {
+    let mut settings = self.settings.clone();
+
+    if let Some(root) = &self.root {
+      let file_path = PathBuf::from(canonicalize(file_path.to_str().unwrap()));
+
+      if let Ok(relative) = file_path.strip_prefix(root) {
+        for entry in &self.overrides {
+          if entry.matcher.is_match(relative) {
+            settings = settings.merge(entry.settings.clone());
+          }
+        }
+      }
+    }
+
+    let cli_settings = cli_settings
+      .clone()
+      .resolve_paths(&PathBuf::from(canonicalize(".")));
+    settings.merge(cli_settings)
+  }
+}
+
+impl CliOptions {
+  pub fn parse(options: &str) -> Result<CliOptions, ConfigError> {
+    if options.trim().is_empty() {
+      return Ok(CliOptions::default());
+    }
+
+    let options: CliOptions =
+      serde_json::from_str(options).map_err(|e| ConfigError::InvalidOptions(e.to_string()))?;
+
+    if let Some(key) = options.unknown.keys().next() {
+      return Err(ConfigError::InvalidOptions(format!(
+        "unknown option {}",
+        key
+      )));
+    }
+
+    Ok(options)
+  }
+}
+
+/// Parses a define given as `NAME` or `NAME=VALUE`, like the `-D` flag of a C compiler. `NAME`
+/// alone defines the macro as `1`.
+pub fn parse_define_flag(flag: &str) -> Result<(String, DefineValue), ConfigError> {
+  let (name, value) = match flag.split_once('=') {
+    Some((name, value)) => (name.trim(), DefineValue::parse(value.trim())),
+    None => (flag.trim(), DefineValue::Bool(true)),
+  };
+
+  let is_identifier = name
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+  if !is_identifier {
+    return Err(ConfigError::InvalidDefine(flag.to_string()));
+  }
+
+  Ok((name.to_string(), value))
+}
+
+/// Parses the `-D` defines and layers them on top of `settings`.
+pub fn apply_define_flags(settings: &mut Settings, flags: &[String]) -> Result<(), ConfigError> {
+  for flag in flags {
+    let (name, value) = parse_define_flag(flag)?;
+    settings
+      .defines
+      .get_or_insert_with(BTreeMap::new)
+      .insert(name, value);
+  }
+
+  Ok(())
+}
+
+pub fn print_config_error(error: &ConfigError) {
+  print_level(Level::ERROR);
+
+  match error {
+    ConfigError::InvalidOptions(message) => {
+      log("Invalid options: ");
+      log(message);
+    }
+    ConfigError::FileNotFound(path) => {
+      log("Config file not found: ");
+      log_with_color(&to_forward_slashes(path), "blue");
+    }
+    ConfigError::CouldNotParseFile(path, message) => {
+      log("Could not parse config file ");
+      log_with_color(&to_forward_slashes(path), "blue");
+      log(": ");
+      log(message);
+    }
+    ConfigError::InvalidGlob(path, glob, message) => {
+      log("Invalid glob ");
+      log_with_color(glob, "blue");
+      log(" in config file ");
+      log_with_color(&to_forward_slashes(path), "blue");
+      log(": ");
+      log(message);
+    }
+    ConfigError::UnknownKeys(path, keys) => {
+      log("Unknown keys in config file ");
+      log_with_color(&to_forward_slashes(path), "blue");
+      log(": ");
+      log(&keys.join(", "));
+    }
+    ConfigError::InvalidDefine(define) => {
+      log("Invalid define ");
+      log_with_color(define, "blue");
+      log(", expected NAME or NAME=VALUE");
+    }
+  }
+
+  logln("");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CliOptions, ConfigError, ConfigFile, NamingConvention};
+
+  #[test]
+  fn collects_the_unknown_keys_of_the_config_file() {
+    let file: ConfigFile = toml::from_str(
+      r#"
+stageSufix = false
+naming = "camel"
+
+[[overrides]]
+files = "*.frag"
+minfy = true
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(file.settings.naming, Some(NamingConvention::Camel));
+    assert_eq!(file.unknown.keys().collect::<Vec<_>>(), ["stageSufix"]);
+    assert_eq!(
+      file.overrides[0].unknown.keys().collect::<Vec<_>>(),
+      ["minfy"]
+    );
+  }
+
+  #[test]
+  fn rejects_unknown_options() {
+    let options = CliOptions::parse(r#"{ "format": "json", "stage_suffix": false }"#).unwrap();
+    assert_eq!(options.settings.format, Some(vec!["json".to_string()]));
+
+    assert!(matches!(
+      CliOptions::parse(r#"{ "formt": "json" }"#),
+      Err(ConfigError::InvalidOptions(_))
+    ));
+  }
+}
@@ -1,19 +1,82 @@
+use super::layout::{self, FieldType, ScalarKind};
+use crate::config::DefineValue;
 use crate::logln;
+use crate::preprocessor::variants::Variant;
 use crate::utils::get_shader_type::ShaderType;
+use crate::utils::strip_comments::strip_comments;
 use glsl::parser::Parse as _;
 use glsl::syntax::{
-  ShaderStage, SingleDeclaration, StorageQualifier, TypeQualifierSpec, TypeSpecifierNonArray,
+  ArraySpecifier, ArraySpecifierDimension, Declaration, Expr, ExternalDeclaration, Initializer,
+  LayoutQualifierSpec, Preprocessor, PreprocessorDefine, ShaderStage, SingleDeclaration,
+  StorageQualifier, StructFieldSpecifier, StructSpecifier, TypeQualifier, TypeQualifierSpec,
+  TypeSpecifierNonArray, UnaryOp,
 };
 use glsl::visitor::{Host, Visit, Visitor};
+use std::collections::BTreeMap;
 
-pub fn capitalize_first_letter(s: &str) -> String {
-  s.chars().next().unwrap().to_uppercase().collect::<String>() + &s[1..]
+/// Gets the name of a type as it is written in GLSL, e.g. `mat2x3`.
+pub fn glsl_type_name(ty: &TypeSpecifierNonArray) -> String {
+  let mut output = String::new();
+  glsl::transpiler::glsl::show_type_specifier_non_array(&mut output, ty);
+  output
+}
+
+/// A shader once every transform ran. `content` is the shader the types are generated from,
+/// and `source` the one embedded in the output, which can be minified or have its names
+/// mangled. `names` maps the original names of the interface to the mangled ones, `defines`
+/// are the defines inserted in the shader and `includes` the files it includes.
+#[derive(Clone, Debug)]
+pub struct ProcessedShader {
+  pub defines: Vec<(String, DefineValue)>,
+  pub includes: Vec<String>,
+  pub content: String,
+  pub source: String,
+  pub names: BTreeMap<String, String>,
+}
+
+/// A variant of a shader, the values of the defines it was compiled with and its interface.
+#[derive(Clone, Debug)]
+pub struct ShaderVariant {
+  pub defines: Variant,
+  pub shader: ProcessedShader,
+  pub data: ShaderData,
 }
 
 #[derive(Clone, Debug)]
 pub struct TypedVariable {
   pub identifier: String,
   pub type_label: TypeSpecifierNonArray,
+  pub is_array: bool,
+  /// The number of elements of an array, if it is known. Sizes given by a constant are resolved.
+  pub array_size: Option<usize>,
+  /// The `layout(location = N)` of an input or an output.
+  pub location: Option<usize>,
+  pub layout: Vec<(String, Option<i64>)>,
+}
+
+/// A struct declared by the shader, which uniform blocks can use as the type of their fields.
+#[derive(Clone, Debug)]
+pub struct StructDefinition {
+  pub name: String,
+  pub fields: Vec<TypedVariable>,
+}
+
+/// A `uniform` interface block.
+#[derive(Clone, Debug)]
+pub struct UniformBlock {
+  pub name: String,
+  pub instance: Option<String>,
+  pub layout: Vec<(String, Option<i64>)>,
+  pub fields: Vec<TypedVariable>,
+}
+
+/// A `const` global or an object-like macro whose value is a literal. `value` is the literal
+/// written the same way in GLSL and in TypeScript, e.g. `8`, `3.14159` or `true`.
+#[derive(Clone, Debug)]
+pub struct Constant {
+  pub identifier: String,
+  pub type_label: TypeSpecifierNonArray,
+  pub value: String,
 }
 
 #[derive(Clone, Debug)]
@@ -21,12 +84,360 @@ pub struct ShaderData {
   pub uniforms: Vec<TypedVariable>,
   pub ins: Vec<TypedVariable>,
   pub outs: Vec<TypedVariable>,
+  pub constants: Vec<Constant>,
+  pub structs: Vec<StructDefinition>,
+  pub blocks: Vec<UniformBlock>,
+  /// The qualifiers of `layout(std140) uniform;`, which the blocks declared after it inherit.
+  pub block_layout: Vec<(String, Option<i64>)>,
   pub shader_type: ShaderType,
 }
 
+/// Parses the value of a macro if it is a single literal, e.g. `8`, `0x10`, `4u`, `2.5f`, `-1.0`
+/// or `true`.
+fn parse_literal(value: &str) -> Option<(TypeSpecifierNonArray, String)> {
+  let value = value.trim();
+  let (sign, digits) = match value.strip_prefix('-') {
+    Some(digits) => ("-", digits.trim_start()),
+    None => ("", value),
+  };
+
+  match digits {
+    "true" | "false" if sign.is_empty() => {
+      return Some((TypeSpecifierNonArray::Bool, digits.to_string()))
+    }
+    _ if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') => return None,
+    _ => {}
+  }
+
+  let lowercase = digits.to_lowercase();
+  let is_float = !lowercase.starts_with("0x")
+    && (lowercase.contains(['.', 'e']) || lowercase.ends_with('f') || lowercase.ends_with("lf"));
+
+  if is_float {
+    let number = lowercase.trim_end_matches("lf").trim_end_matches('f');
+    let number = number.parse::<f32>().ok()?;
+    return Some((
+      TypeSpecifierNonArray::Float,
+      format!("{}{:?}", sign, number),
+    ));
+  }
+
+  let (type_label, number) = match lowercase.strip_suffix('u') {
+    Some(number) => (TypeSpecifierNonArray::UInt, number),
+    None => (TypeSpecifierNonArray::Int, lowercase.as_str()),
+  };
+
+  let number = if let Some(hex) = number.strip_prefix("0x") {
+    i64::from_str_radix(hex, 16).ok()?
+  } else if number.len() > 1 && number.starts_with('0') {
+    i64::from_str_radix(&number[1..], 8).ok()?
+  } else {
+    number.parse::<i64>().ok()?
+  };
+
+  Some((type_label, format!("{}{}", sign, number)))
+}
+
+/// Formats the value of a `float` or `double` constant as a floating point literal, which it is
+/// not when the constant is written with an integer, as in `const float K = 1;`.
+pub fn float_literal(value: &str) -> String {
+  if value.parse::<i64>().is_ok() {
+    format!("{}.0", value)
+  } else {
+    value.to_string()
+  }
+}
+
+/// Gets the value of the initializer of a `const` global if it is a literal.
+fn literal_initializer(initializer: &Option<Initializer>) -> Option<String> {
+  let expr = match initializer {
+    Some(Initializer::Simple(expr)) => expr.as_ref(),
+    _ => return None,
+  };
+
+  let (sign, expr) = match expr {
+    Expr::Unary(UnaryOp::Minus, expr) => ("-", expr.as_ref()),
+    expr => ("", expr),
+  };
+
+  let value = match expr {
+    Expr::IntConst(value) => value.to_string(),
+    Expr::UIntConst(value) => value.to_string(),
+    Expr::FloatConst(value) => format!("{:?}", value),
+    Expr::DoubleConst(value) => format!("{:?}", value),
+    Expr::BoolConst(value) if sign.is_empty() => value.to_string(),
+    _ => return None,
+  };
+
+  Some(format!("{}{}", sign, value))
+}
+
+/// Gets the identifiers of the `layout` qualifiers of a declaration, and their values if they
+/// are integers, e.g. `[("std140", None), ("binding", Some(2))]`.
+fn layout_qualifiers(qualifier: Option<&TypeQualifier>) -> Vec<(String, Option<i64>)> {
+  qualifier
+    .iter()
+    .flat_map(|qualifier| &qualifier.qualifiers.0)
+    .filter_map(|spec| match spec {
+      TypeQualifierSpec::Layout(layout) => Some(&layout.ids.0),
+      _ => None,
+    })
+    .flatten()
+    .map(|id| match id {
+      LayoutQualifierSpec::Identifier(name, value) => {
+        let value = value.as_ref().and_then(|value| match value.as_ref() {
+          Expr::IntConst(value) => Some(*value as i64),
+          Expr::UIntConst(value) => Some(*value as i64),
+          _ => None,
+        });
+        (name.to_string(), value)
+      }
+      LayoutQualifierSpec::Shared => ("shared".to_string(), None),
+    })
+    .collect()
+}
+
+/// The qualifiers that set how the fields of a block are laid out in its buffer.
+const PACKING_QUALIFIERS: [&str; 4] = ["shared", "packed", "std140", "std430"];
+
+fn is_packing(name: &str) -> bool {
+  PACKING_QUALIFIERS.contains(&name)
+}
+
+/// Adds the default qualifiers to the ones of a block, unless the block sets them itself.
+fn inherit_layout(
+  mut layout: Vec<(String, Option<i64>)>,
+  defaults: &[(String, Option<i64>)],
+) -> Vec<(String, Option<i64>)> {
+  let has_packing = layout.iter().any(|(name, _)| is_packing(name));
+  let inherited = defaults
+    .iter()
+    .filter(|(name, _)| !(has_packing && is_packing(name)))
+    .filter(|(name, _)| layout.iter().all(|(other, _)| other != name))
+    .cloned()
+    .collect::<Vec<(String, Option<i64>)>>();
+
+  layout.splice(0..0, inherited);
+  layout
+}
+
+/// Gets the packing of a block, `shared` when it has none, as in GLSL.
+pub fn block_packing(layout: &[(String, Option<i64>)]) -> &str {
+  layout
+    .iter()
+    .rev()
+    .map(|(name, _)| name.as_str())
+    .find(|name| is_packing(name))
+    .unwrap_or("shared")
+}
+
+/// Gets the value of a `layout` qualifier such as `location` or `binding`.
+pub fn layout_value(layout: &[(String, Option<i64>)], name: &str) -> Option<usize> {
+  layout
+    .iter()
+    .find(|(other, _)| other == name)
+    .and_then(|(_, value)| usize::try_from((*value)?).ok())
+}
+
+impl TypedVariable {
+  /// Gets the type of the components of the variable, e.g. `Int` for an `ivec2`, or `None` for
+  /// structs and opaque types. Integer attributes have to be bound with `vertexAttribIPointer`.
+  pub fn scalar_kind(&self) -> Option<ScalarKind> {
+    match layout::field_type(&self.type_label)? {
+      FieldType::Value { kind, .. } => Some(kind),
+      FieldType::Struct(_) => None,
+    }
+  }
+}
+
+impl ShaderData {
+  /// Sorts the uniforms, inputs, outputs and constants by name. The fields of structs and blocks
+  /// keep their order, which their layout depends on.
+  pub fn sort_by_name(&mut self) {
+    self
+      .uniforms
+      .sort_by(|a, b| a.identifier.cmp(&b.identifier));
+    self.ins.sort_by(|a, b| a.identifier.cmp(&b.identifier));
+    self.outs.sort_by(|a, b| a.identifier.cmp(&b.identifier));
+    self
+      .constants
+      .sort_by(|a, b| a.identifier.cmp(&b.identifier));
+  }
+
+  /// Adds a constant, replacing a previous one with the same name (e.g. a redefined macro).
+  fn push_constant(&mut self, constant: Constant) {
+    self
+      .constants
+      .retain(|other| other.identifier != constant.identifier);
+    self.constants.push(constant);
+  }
+
+  /// Gets the number of elements of an array. Arrays of arrays are counted as a flat array, and
+  /// sizes given by an integer constant are resolved. Returns `None` when the size is unknown.
+  fn array_size(&self, specifiers: &[&Option<ArraySpecifier>]) -> Option<usize> {
+    let dimensions = specifiers
+      .iter()
+      .filter_map(|specifier| specifier.as_ref())
+      .flat_map(|specifier| &specifier.dimensions.0)
+      .collect::<Vec<&ArraySpecifierDimension>>();
+
+    if dimensions.is_empty() {
+      return None;
+    }
+
+    dimensions.iter().try_fold(1, |size, dimension| {
+      let length = match dimension {
+        ArraySpecifierDimension::ExplicitlySized(expr) => match expr.as_ref() {
+          Expr::IntConst(value) => usize::try_from(*value).ok()?,
+          Expr::UIntConst(value) => *value as usize,
+          Expr::Variable(name) => self
+            .constants
+            .iter()
+            .find(|constant| constant.identifier == name.as_str())?
+            .value
+            .parse::<usize>()
+            .ok()?,
+          _ => return None,
+        },
+        ArraySpecifierDimension::Unsized => return None,
+      };
+
+      Some(size * length)
+    })
+  }
+
+  fn struct_fields(&self, fields: &[StructFieldSpecifier]) -> Vec<TypedVariable> {
+    fields
+      .iter()
+      .flat_map(|field| {
+        field.identifiers.0.iter().map(|identifier| TypedVariable {
+          identifier: identifier.ident.to_string(),
+          type_label: field.ty.ty.clone(),
+          is_array: field.ty.array_specifier.is_some() || identifier.array_spec.is_some(),
+          array_size: self.array_size(&[&field.ty.array_specifier, &identifier.array_spec]),
+          location: None,
+          layout: layout_qualifiers(field.qualifier.as_ref()),
+        })
+      })
+      .collect()
+  }
+}
+
 impl Visitor for ShaderData {
+  fn visit_external_declaration(&mut self, declaration: &ExternalDeclaration) -> Visit {
+    // Structs are collected here, as `visit_single_declaration` does not visit its children
+    if let ExternalDeclaration::Declaration(Declaration::InitDeclaratorList(list)) = declaration {
+      if let TypeSpecifierNonArray::Struct(StructSpecifier {
+        name: Some(name),
+        fields,
+      }) = &list.head.ty.ty.ty
+      {
+        self.structs.push(StructDefinition {
+          name: name.to_string(),
+          fields: self.struct_fields(&fields.0),
+        });
+      }
+    }
+
+    match declaration {
+      ExternalDeclaration::Preprocessor(Preprocessor::Define(PreprocessorDefine::ObjectLike {
+        ident,
+        value,
+      })) => {
+        if let Some((type_label, value)) = parse_literal(value) {
+          self.push_constant(Constant {
+            identifier: ident.to_string(),
+            type_label,
+            value,
+          });
+        }
+      }
+      ExternalDeclaration::Preprocessor(Preprocessor::Undef(undef)) => {
+        let name = undef.name.to_string();
+        self.constants.retain(|other| other.identifier != name);
+      }
+      ExternalDeclaration::Declaration(Declaration::InitDeclaratorList(list))
+        if list.head.ty.ty.array_specifier.is_none()
+          && list.head.ty.qualifier.iter().any(|qualifier| {
+            qualifier
+              .qualifiers
+              .0
+              .contains(&TypeQualifierSpec::Storage(StorageQualifier::Const))
+          }) =>
+      {
+        let head = list
+          .head
+          .name
+          .iter()
+          .filter(|_| list.head.array_specifier.is_none())
+          .map(|name| (name, &list.head.initializer));
+        let tail = list
+          .tail
+          .iter()
+          .filter(|tail| tail.ident.array_spec.is_none())
+          .map(|tail| (&tail.ident.ident, &tail.initializer));
+
+        for (name, initializer) in head.chain(tail) {
+          if let Some(value) = literal_initializer(initializer) {
+            self.push_constant(Constant {
+              identifier: name.to_string(),
+              type_label: list.head.ty.ty.ty.clone(),
+              value,
+            });
+          }
+        }
+      }
+      ExternalDeclaration::Declaration(Declaration::Global(qualifier, identifiers))
+        if identifiers.is_empty()
+          && qualifier
+            .qualifiers
+            .0
+            .contains(&TypeQualifierSpec::Storage(StorageQualifier::Uniform)) =>
+      {
+        self.block_layout = inherit_layout(layout_qualifiers(Some(qualifier)), &self.block_layout);
+      }
+      ExternalDeclaration::Declaration(Declaration::Block(block))
+        if block
+          .qualifier
+          .qualifiers
+          .0
+          .contains(&TypeQualifierSpec::Storage(StorageQualifier::Uniform)) =>
+      {
+        self.blocks.push(UniformBlock {
+          name: block.name.to_string(),
+          instance: block
+            .identifier
+            .as_ref()
+            .map(|identifier| identifier.ident.to_string()),
+          layout: inherit_layout(
+            layout_qualifiers(Some(&block.qualifier)),
+            &self.block_layout,
+          ),
+          fields: self.struct_fields(&block.fields),
+        });
+      }
+      _ => {}
+    }
+
+    Visit::Children
+  }
+
   fn visit_single_declaration(&mut self, declaration: &SingleDeclaration) -> Visit {
     if let Some(name) = &declaration.name {
+      let layout = layout_qualifiers(declaration.ty.qualifier.as_ref());
+      let variable = TypedVariable {
+        identifier: name.as_str().to_string(),
+        type_label: declaration.ty.ty.ty.clone(),
+        is_array: declaration.ty.ty.array_specifier.is_some()
+          || declaration.array_specifier.is_some(),
+        array_size: self.array_size(&[
+          &declaration.ty.ty.array_specifier,
+          &declaration.array_specifier,
+        ]),
+        location: layout_value(&layout, "location"),
+        layout,
+      };
+
       if let Some(type_qualifier) = &declaration.ty.qualifier {
         type_qualifier
           .qualifiers
@@ -35,25 +446,16 @@ impl Visitor for ShaderData {
           .for_each(|qualifier| {
             if let TypeQualifierSpec::Storage(storage_qualifier) = qualifier {
               if storage_qualifier == StorageQualifier::Uniform {
-                self.uniforms.push(TypedVariable {
-                  identifier: name.as_str().to_string(),
-                  type_label: declaration.ty.ty.ty.clone(),
-                });
+                self.uniforms.push(variable.clone());
               }
 
               match self.shader_type {
                 _ => {
                   if storage_qualifier == StorageQualifier::Out {
-                    self.outs.push(TypedVariable {
-                      identifier: name.as_str().to_string(),
-                      type_label: declaration.ty.ty.ty.clone(),
-                    });
+                    self.outs.push(variable.clone());
                   }
                   if storage_qualifier == StorageQualifier::In {
-                    self.ins.push(TypedVariable {
-                      identifier: name.as_str().to_string(),
-                      type_label: declaration.ty.ty.ty.clone(),
-                    });
+                    self.ins.push(variable.clone());
                   }
                 }
               }
@@ -68,12 +470,16 @@ impl Visitor for ShaderData {
 
 pub fn extract_shader_data(file: &String, shader_type: ShaderType) -> ShaderData {
   let stage: Result<glsl::syntax::TranslationUnit, glsl::parser::ParseError> =
-    ShaderStage::parse(file);
+    ShaderStage::parse(strip_comments(file));
 
   let mut shader_data = ShaderData {
     uniforms: Vec::new(),
     ins: Vec::new(),
     outs: Vec::new(),
+    constants: Vec::new(),
+    structs: Vec::new(),
+    blocks: Vec::new(),
+    block_layout: Vec::new(),
     shader_type,
   };
 
@@ -0,0 +1,434 @@
+use super::common::{self, ShaderData, TypedVariable};
+use super::layout::{self, FieldType, Member, ScalarKind, ShaderLayouts, StructLayout};
+use super::vertex_layout::attribute_locations;
+use super::{Generator, OutputFile, Reflection};
+use crate::config::Settings;
+use crate::utils::get_shader_type::ShaderType;
+use glsl::syntax::TypeSpecifierNonArray;
+use std::path::PathBuf;
+
+/// The keywords of C++, which can not be used as names.
+const KEYWORDS: [&str; 92] = [
+  "alignas",
+  "alignof",
+  "and",
+  "and_eq",
+  "asm",
+  "auto",
+  "bitand",
+  "bitor",
+  "bool",
+  "break",
+  "case",
+  "catch",
+  "char",
+  "char8_t",
+  "char16_t",
+  "char32_t",
+  "class",
+  "compl",
+  "concept",
+  "const",
+  "consteval",
+  "constexpr",
+  "constinit",
+  "const_cast",
+  "continue",
+  "co_await",
+  "co_return",
+  "co_yield",
+  "decltype",
+  "default",
+  "delete",
+  "do",
+  "double",
+  "dynamic_cast",
+  "else",
+  "enum",
+  "explicit",
+  "export",
+  "extern",
+  "false",
+  "float",
+  "for",
+  "friend",
+  "goto",
+  "if",
+  "inline",
+  "int",
+  "long",
+  "mutable",
+  "namespace",
+  "new",
+  "noexcept",
+  "not",
+  "not_eq",
+  "nullptr",
+  "operator",
+  "or",
+  "or_eq",
+  "private",
+  "protected",
+  "public",
+  "register",
+  "reinterpret_cast",
+  "requires",
+  "return",
+  "short",
+  "signed",
+  "sizeof",
+  "static",
+  "static_assert",
+  "static_cast",
+  "struct",
+  "switch",
+  "template",
+  "this",
+  "thread_local",
+  "throw",
+  "true",
+  "try",
+  "typedef",
+  "typeid",
+  "typename",
+  "union",
+  "unsigned",
+  "using",
+  "virtual",
+  "void",
+  "volatile",
+  "wchar_t",
+  "while",
+  "xor",
+  "xor_eq",
+];
+
+pub struct CppGenerator;
+
+impl Generator for CppGenerator {
+  fn name(&self) -> &str {
+    "cpp"
+  }
+
+  /// Generates a C++ header for a shader: its source, its constants, the locations of its vertex
+  /// attributes and aligned structs for its uniform blocks, in a namespace named after the file.
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    _settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let shader = &reflection.shader;
+    let shader_data = &reflection.data;
+    let layouts = layout::shader_layouts(shader_data)
+      .map_err(|error| format!("could not lay out the uniform blocks: {}", error))?;
+
+    let namespace = namespace(&reflection.name);
+    check_names(shader_data, &layouts)?;
+
+    let mut output_file = String::new();
+    output_file.push_str("// DO NOT EDIT THIS FILE\n");
+    output_file.push_str("// This file is generated by glsl-types\n\n");
+    output_file.push_str("#pragma once\n\n#include <cstddef>\n#include <cstdint>\n\n");
+    output_file.push_str(&format!("namespace {} {{\n\n", namespace));
+
+    output_file.push_str(&format!(
+      "inline constexpr const char* SOURCE = {};\n",
+      raw_string(&shader.source)
+    ));
+
+    if !shader_data.constants.is_empty() {
+      output_file.push('\n');
+    }
+    for constant in &shader_data.constants {
+      let label = convert_glsl_to_cpp_label(&constant.type_label).ok_or_else(|| {
+        format!(
+          "the constant {} is a {}, which has no C++ type",
+          constant.identifier,
+          common::glsl_type_name(&constant.type_label)
+        )
+      })?;
+
+      output_file.push_str(&format!(
+        "inline constexpr {} {} = {};\n",
+        label,
+        identifier(&constant.identifier),
+        format_constant_value(&constant.type_label, &constant.value)
+      ));
+    }
+
+    if shader_data.shader_type == ShaderType::Vertex && !shader_data.ins.is_empty() {
+      output_file.push_str(&format_attribute_locations(&shader_data.ins));
+    }
+
+    for layout in layouts.all() {
+      output_file.push_str(&format_struct(layout));
+    }
+
+    output_file.push_str(&format!("\n}} // namespace {}\n", namespace));
+
+    Ok(vec![OutputFile {
+      path: PathBuf::from(format!("{}.h", reflection.name)),
+      contents: output_file,
+    }])
+  }
+}
+
+/// Formats a raw string literal with a delimiter the source does not contain.
+fn raw_string(source: &str) -> String {
+  let mut delimiter = String::from("glsl");
+  while source.contains(&format!("){}\"", delimiter)) {
+    delimiter.push('_');
+  }
+
+  format!("R\"{}({}){}\"", delimiter, source, delimiter)
+}
+
+/// Escapes a name that is a keyword of C++ with a `_` suffix, as `class_` for `class`.
+fn identifier(name: &str) -> String {
+  if KEYWORDS.contains(&name) {
+    format!("{}_", name)
+  } else {
+    name.to_string()
+  }
+}
+
+/// Gets the namespace of a file from its name, e.g. `_2d_blit_frag` for `2d_blit_frag`. Names
+/// that do not start with a letter get a `_` prefix, and keywords a `_` suffix.
+fn namespace(name: &str) -> String {
+  let name = name
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+    .collect::<String>();
+
+  if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+    identifier(&name)
+  } else {
+    format!("_{}", name)
+  }
+}
+
+/// Checks that no struct, block or constant of the shader is named like `SOURCE` or like the
+/// location of a vertex attribute, which the header declares in the same namespace.
+fn check_names(shader_data: &ShaderData, layouts: &ShaderLayouts) -> Result<(), String> {
+  let mut generated = vec![String::from("SOURCE")];
+  if shader_data.shader_type == ShaderType::Vertex {
+    generated.extend(
+      shader_data
+        .ins
+        .iter()
+        .map(|input| location_name(&input.identifier)),
+    );
+  }
+
+  let names = layouts
+    .structs
+    .iter()
+    .map(|layout| ("struct", &layout.name))
+    .chain(
+      layouts
+        .blocks
+        .iter()
+        .map(|layout| ("uniform block", &layout.name)),
+    )
+    .chain(
+      shader_data
+        .constants
+        .iter()
+        .map(|constant| ("constant", &constant.identifier)),
+    );
+
+  for (kind, name) in names {
+    if generated.contains(&identifier(name)) {
+      return Err(format!(
+        "the {} {} has the name of a declaration of the generated header, rename it",
+        kind, name
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+fn format_constant_value(type_label: &TypeSpecifierNonArray, value: &str) -> String {
+  match type_label {
+    TypeSpecifierNonArray::Float => format!("{}f", common::float_literal(value)),
+    TypeSpecifierNonArray::Double => common::float_literal(value),
+    TypeSpecifierNonArray::UInt => format!("{}u", value),
+    _ => value.to_string(),
+  }
+}
+
+/// Formats the locations of the inputs of a vertex shader, as `NAME_LOCATION` constants. Inputs
+/// without a `layout(location = N)` get the next free locations, to bind them with
+/// `glBindAttribLocation` before linking.
+fn format_attribute_locations(ins: &[TypedVariable]) -> String {
+  let locations = ins
+    .iter()
+    .zip(attribute_locations(ins))
+    .map(|(input, location)| {
+      format!(
+        "inline constexpr unsigned int {} = {};",
+        location_name(&input.identifier),
+        location
+      )
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  format!("\n{}\n", locations)
+}
+
+/// Gets the name of the location of a vertex attribute, e.g. `A_POSITION_LOCATION`.
+fn location_name(name: &str) -> String {
+  format!("{}_LOCATION", to_screaming_snake_case(name))
+}
+
+fn to_screaming_snake_case(name: &str) -> String {
+  let mut output = String::new();
+  for (i, c) in name.chars().enumerate() {
+    if c.is_ascii_uppercase() && i > 0 && !output.ends_with('_') {
+      output.push('_');
+    }
+    output.push(c.to_ascii_uppercase());
+  }
+
+  output
+}
+
+fn scalar_label(kind: ScalarKind) -> &'static str {
+  match kind {
+    ScalarKind::Float => "float",
+    // Booleans are 4 bytes in buffers
+    ScalarKind::Int | ScalarKind::Bool => "int32_t",
+    ScalarKind::UInt => "uint32_t",
+  }
+}
+
+/// Formats a uniform block or a struct with the padding of std140 as explicit members, and
+/// checks its layout with `static_assert`s.
+fn format_struct(layout: &StructLayout) -> String {
+  let mut fields = Vec::new();
+  let mut assertions = Vec::new();
+
+  for (i, member) in layout.members().iter().enumerate() {
+    let field = match member {
+      Member::Field(field) => field,
+      Member::Padding(size) => {
+        fields.push(format!("  uint8_t _pad{}[{}];", i, size));
+        continue;
+      }
+    };
+
+    let element = match &field.ty {
+      FieldType::Value { kind, .. } => scalar_label(*kind).to_string(),
+      FieldType::Struct(name) => name.clone(),
+    };
+    let dimensions = field
+      .dimensions()
+      .iter()
+      .map(|length| format!("[{}]", length))
+      .collect::<String>();
+
+    let name = identifier(&field.name);
+    fields.push(format!("  {} {}{};", element, name, dimensions));
+    assertions.push(format!(
+      "static_assert(offsetof({}, {}) == {}, \"std140 offset of {}.{}\");",
+      layout.name, name, field.offset, layout.name, field.name
+    ));
+  }
+
+  assertions.push(format!(
+    "static_assert(sizeof({}) == {}, \"std140 size of {}\");",
+    layout.name, layout.size, layout.name
+  ));
+
+  format!(
+    r#"
+struct alignas(16) {} {{
+{}
+}};
+
+{}
+"#,
+    layout.name,
+    fields.join("\n"),
+    assertions.join("\n")
+  )
+}
+
+/// Gets the C++ type of a constant, or `None` for the types constants can not have.
+fn convert_glsl_to_cpp_label(ty: &TypeSpecifierNonArray) -> Option<&'static str> {
+  match ty {
+    TypeSpecifierNonArray::Float => Some("float"),
+    TypeSpecifierNonArray::Double => Some("double"),
+    TypeSpecifierNonArray::Int => Some("int32_t"),
+    TypeSpecifierNonArray::UInt => Some("uint32_t"),
+    TypeSpecifierNonArray::Bool => Some("bool"),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::CppGenerator;
+  use crate::config::Settings;
+  use crate::generator::{test_reflection, Generator};
+  use crate::utils::get_shader_type::ShaderType;
+
+  const FRAGMENT: &str = "#version 300 es
+precision highp float;
+void main() {}
+";
+
+  fn generate(name: &str, source: &str, stage: ShaderType) -> Result<String, String> {
+    let reflection = test_reflection(name, source, stage);
+    let files = CppGenerator.generate(&reflection, &Settings::default())?;
+
+    Ok(files[0].contents.clone())
+  }
+
+  #[test]
+  fn names_the_namespace_after_the_file() {
+    let output = generate("blur_frag", FRAGMENT, ShaderType::Fragment).unwrap();
+    assert!(output.contains("namespace blur_frag {"), "{}", output);
+  }
+
+  #[test]
+  fn prefixes_a_namespace_that_starts_with_a_digit() {
+    let output = generate("2d-blit_frag", FRAGMENT, ShaderType::Fragment).unwrap();
+    assert!(output.contains("namespace _2d_blit_frag {"), "{}", output);
+  }
+
+  #[test]
+  fn escapes_a_namespace_that_is_a_keyword() {
+    let output = generate("class", FRAGMENT, ShaderType::Fragment).unwrap();
+    assert!(output.contains("namespace class_ {"), "{}", output);
+  }
+
+  #[test]
+  fn rejects_constants_named_like_a_declaration_of_the_header() {
+    let error = generate(
+      "lit_frag",
+      "#version 300 es
+precision highp float;
+#define SOURCE 1
+void main() {}
+",
+      ShaderType::Fragment,
+    )
+    .unwrap_err();
+    assert!(error.contains("constant SOURCE"), "{}", error);
+
+    let error = generate(
+      "lit_vert",
+      "#version 300 es
+in vec3 aPosition;
+const int A_POSITION_LOCATION = 3;
+void main() { gl_Position = vec4(aPosition, 1.0); }
+",
+      ShaderType::Vertex,
+    )
+    .unwrap_err();
+    assert!(error.contains("constant A_POSITION_LOCATION"), "{}", error);
+  }
+}
@@ -0,0 +1,222 @@
+use super::naming;
+use super::OutputFile;
+use crate::config::NamingConvention;
+use crate::config::{Emit, ModuleFormat, Settings};
+use std::path::PathBuf;
+
+/// A shader listed in the index: the name of its module, without extension, and of its export.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IndexEntry {
+  pub module: String,
+  pub export: String,
+}
+
+/// An entry as it is imported in the index, as `local`.
+struct Import<'a> {
+  module: &'a str,
+  export: &'a str,
+  local: String,
+}
+
+/// Generates the index of the TypeScript outputs, which re-exports every shader and maps their
+/// names to them in `SHADERS`, with the `ShaderMap` and `ShaderName` types. The entries are
+/// sorted, so the index only changes when the set of shaders does.
+pub fn generate_index(entries: &[IndexEntry], settings: &Settings) -> Vec<OutputFile> {
+  let mut entries = entries.to_vec();
+  entries.sort();
+  entries.dedup_by(|entry, other| entry.module == other.module);
+  let entries = imports(&entries);
+
+  match settings.emit() {
+    Emit::Ts => vec![OutputFile {
+      path: PathBuf::from("index.ts"),
+      contents: format_index(&entries, "", settings.as_const()),
+    }],
+    Emit::Js => vec![
+      OutputFile {
+        path: PathBuf::from("index.js"),
+        contents: match settings.module() {
+          ModuleFormat::Esm => format_index(&entries, ".js", false),
+          ModuleFormat::Cjs => format_commonjs_index(&entries),
+        },
+      },
+      OutputFile {
+        path: PathBuf::from("index.d.ts"),
+        contents: format_index_declarations(&entries, settings.as_const()),
+      },
+    ],
+  }
+}
+
+/// Gets the names the entries are imported as. Shaders of different folders can have the same
+/// export, e.g. `post/blur.frag` and `ui/blur.frag`, which are then prefixed with their folders,
+/// as `PostBlurFrag` and `UiBlurFrag`.
+fn imports(entries: &[IndexEntry]) -> Vec<Import<'_>> {
+  entries
+    .iter()
+    .map(|entry| {
+      let is_ambiguous = entries
+        .iter()
+        .filter(|other| other.export == entry.export)
+        .count()
+        > 1;
+
+      let local = if is_ambiguous {
+        let (folders, _) = entry.module.rsplit_once('/').unwrap_or_default();
+        let naming = if entry.export.starts_with(char::is_lowercase) {
+          NamingConvention::Camel
+        } else {
+          NamingConvention::Pascal
+        };
+
+        naming::identifier(&[folders, &entry.export], naming)
+      } else {
+        entry.export.clone()
+      };
+
+      Import {
+        module: &entry.module,
+        export: &entry.export,
+        local,
+      }
+    })
+    .collect()
+}
+
+/// Formats the key of a shader in `SHADERS`, which is quoted when it is not an identifier,
+/// e.g. for `post-process.frag`.
+fn property_key(name: &str) -> String {
+  let is_identifier = name
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+    && name
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+
+  if is_identifier {
+    name.to_string()
+  } else {
+    serde_json::to_string(name).unwrap()
+  }
+}
+
+fn format_header() -> String {
+  let mut output_file = String::new();
+  output_file.push_str("// DO NOT EDIT THIS FILE\n");
+  output_file.push_str("// This file is generated by glsl-types\n\n");
+  output_file
+}
+
+fn format_imports(entries: &[Import], extension: &str) -> String {
+  entries
+    .iter()
+    .map(|entry| {
+      let name = if entry.local == entry.export {
+        entry.local.clone()
+      } else {
+        format!("{} as {}", entry.export, entry.local)
+      };
+
+      format!(
+        "import {{ {} }} from \"./{}{}\";\n",
+        name, entry.module, extension
+      )
+    })
+    .collect()
+}
+
+fn format_exports(entries: &[Import]) -> String {
+  entries
+    .iter()
+    .map(|entry| entry.local.as_str())
+    .collect::<Vec<&str>>()
+    .join(", ")
+}
+
+/// Formats the fields of `SHADERS`, with `format_field` formatting the key and the export of a
+/// shader as a field of the object or of its type.
+fn format_shaders_fields(
+  entries: &[Import],
+  format_field: impl Fn(&str, &str) -> String,
+) -> String {
+  entries
+    .iter()
+    .map(|entry| {
+      format!(
+        "  {}\n",
+        format_field(&property_key(entry.module), &entry.local)
+      )
+    })
+    .collect()
+}
+
+const SHADER_TYPES: &str = r#"export type ShaderMap = typeof SHADERS;
+export type ShaderName = keyof ShaderMap;
+"#;
+
+/// Formats the index as a `.ts` module, or as an ES module when `extension` is `.js`.
+fn format_index(entries: &[Import], extension: &str, as_const: bool) -> String {
+  let mut output_file = format_header();
+  output_file.push_str(&format_imports(entries, extension));
+
+  output_file.push_str(&format!(
+    "\nexport {{ {} }};\n\nexport const SHADERS = {{\n{}}}{};\n",
+    format_exports(entries),
+    format_shaders_fields(entries, |key, export| format!("{}: {},", key, export)),
+    if as_const { " as const" } else { "" }
+  ));
+
+  // Types only exist in the `.ts` output, the `.js` one has them in its `.d.ts` file
+  if extension.is_empty() {
+    output_file.push_str(&format!("\n{}", SHADER_TYPES));
+  }
+
+  output_file
+}
+
+fn format_commonjs_index(entries: &[Import]) -> String {
+  let mut output_file = format_header();
+  output_file.push_str("\"use strict\";\n\n");
+
+  for entry in entries {
+    let name = if entry.local == entry.export {
+      entry.local.clone()
+    } else {
+      format!("{}: {}", entry.export, entry.local)
+    };
+
+    output_file.push_str(&format!(
+      "const {{ {} }} = require(\"./{}.js\");\n",
+      name, entry.module
+    ));
+  }
+
+  output_file.push_str(&format!(
+    "\nconst SHADERS = {{\n{}}};\n\nmodule.exports = {{ {}SHADERS }};\n",
+    format_shaders_fields(entries, |key, export| format!("{}: {},", key, export)),
+    entries
+      .iter()
+      .map(|entry| format!("{}, ", entry.local))
+      .collect::<String>()
+  ));
+
+  output_file
+}
+
+fn format_index_declarations(entries: &[Import], as_const: bool) -> String {
+  let readonly = if as_const { "readonly " } else { "" };
+  let mut output_file = format_header();
+  output_file.push_str(&format_imports(entries, ".js"));
+
+  output_file.push_str(&format!(
+    "\nexport {{ {} }};\n\nexport declare const SHADERS: {{\n{}}};\n\n{}",
+    format_exports(entries),
+    format_shaders_fields(entries, |key, export| {
+      format!("{}{}: typeof {};", readonly, key, export)
+    }),
+    SHADER_TYPES
+  ));
+
+  output_file
+}
@@ -0,0 +1,213 @@
+use super::common::{self, TypedVariable};
+use super::layout::{self, StructLayout};
+use super::{Generator, OutputFile, Reflection};
+use crate::config::{DefineValue, Settings};
+use crate::utils::get_shader_type::ShaderType;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The version of the schema of the reflection. It changes when a field is removed or changes
+/// its meaning, not when one is added.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The reflection of a shader as it is serialized.
+#[derive(Serialize)]
+pub struct JsonReflection<'a> {
+  version: u32,
+  name: &'a str,
+  stage: ShaderType,
+  defines: BTreeMap<&'a str, Value>,
+  includes: &'a [String],
+  constants: Vec<ReflectedConstant<'a>>,
+  uniforms: Vec<ReflectedVariable<'a>>,
+  samplers: Vec<ReflectedVariable<'a>>,
+  inputs: Vec<ReflectedVariable<'a>>,
+  outputs: Vec<ReflectedVariable<'a>>,
+  structs: Vec<ReflectedStruct<'a>>,
+  blocks: Vec<ReflectedBlock<'a>>,
+  names: &'a BTreeMap<String, String>,
+  source: &'a str,
+}
+
+#[derive(Serialize)]
+struct ReflectedConstant<'a> {
+  name: &'a str,
+  #[serde(rename = "type")]
+  type_label: String,
+  value: Value,
+}
+
+#[derive(Serialize)]
+struct ReflectedVariable<'a> {
+  name: &'a str,
+  #[serde(rename = "type")]
+  type_label: String,
+  /// The type of the components: `float`, `int`, `uint` or `bool`, or `null` for structs and
+  /// opaque types.
+  kind: Option<&'static str>,
+  array_size: Option<usize>,
+  layout: BTreeMap<&'a str, Option<i64>>,
+}
+
+#[derive(Serialize)]
+struct ReflectedField<'a> {
+  name: &'a str,
+  #[serde(rename = "type")]
+  type_label: String,
+  array_size: Option<usize>,
+  offset: usize,
+  size: usize,
+}
+
+#[derive(Serialize)]
+struct ReflectedStruct<'a> {
+  name: &'a str,
+  size: usize,
+  fields: Vec<ReflectedField<'a>>,
+}
+
+#[derive(Serialize)]
+struct ReflectedBlock<'a> {
+  name: &'a str,
+  instance: Option<&'a str>,
+  layout: BTreeMap<&'a str, Option<i64>>,
+  size: usize,
+  fields: Vec<ReflectedField<'a>>,
+}
+
+/// Gets the reflection of a shader in the shape of the JSON schema.
+pub fn reflect(reflection: &Reflection) -> Result<JsonReflection<'_>, String> {
+  let shader = &reflection.shader;
+  let shader_data = &reflection.data;
+  let layouts = layout::shader_layouts(shader_data)
+    .map_err(|error| format!("could not lay out the uniform blocks: {}", error))?;
+
+  // Samplers and other opaque types can not be in a buffer, so they are listed on their own
+  let (samplers, uniforms): (Vec<&TypedVariable>, Vec<&TypedVariable>) = shader_data
+    .uniforms
+    .iter()
+    .partition(|uniform| layout::field_type(&uniform.type_label).is_none());
+
+  Ok(JsonReflection {
+    version: SCHEMA_VERSION,
+    name: &reflection.name,
+    stage: reflection.stage,
+    defines: shader
+      .defines
+      .iter()
+      .map(|(name, value)| (name.as_str(), define_value(value)))
+      .collect(),
+    includes: &shader.includes,
+    constants: shader_data
+      .constants
+      .iter()
+      .map(|constant| ReflectedConstant {
+        name: &constant.identifier,
+        type_label: common::glsl_type_name(&constant.type_label),
+        value: serde_json::from_str(&constant.value).unwrap_or(Value::Null),
+      })
+      .collect(),
+    uniforms: uniforms.into_iter().map(reflect_variable).collect(),
+    samplers: samplers.into_iter().map(reflect_variable).collect(),
+    inputs: shader_data.ins.iter().map(reflect_variable).collect(),
+    outputs: shader_data.outs.iter().map(reflect_variable).collect(),
+    structs: shader_data
+      .structs
+      .iter()
+      .filter_map(|definition| {
+        let layout = layouts
+          .structs
+          .iter()
+          .find(|layout| layout.name == definition.name)?;
+
+        Some(ReflectedStruct {
+          name: &definition.name,
+          size: layout.size,
+          fields: reflect_fields(layout, &definition.fields),
+        })
+      })
+      .collect(),
+    blocks: layouts
+      .blocks
+      .iter()
+      .zip(&shader_data.blocks)
+      .map(|(layout, block)| ReflectedBlock {
+        name: &block.name,
+        instance: block.instance.as_deref(),
+        layout: reflect_layout(&block.layout),
+        size: layout.size,
+        fields: reflect_fields(layout, &block.fields),
+      })
+      .collect(),
+    names: &shader.names,
+    source: &shader.source,
+  })
+}
+
+pub struct JsonGenerator;
+
+impl Generator for JsonGenerator {
+  fn name(&self) -> &str {
+    "json"
+  }
+
+  /// Generates the reflection of a shader as JSON, for tools written in other languages.
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    _settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let output_file = serde_json::to_string_pretty(&reflect(reflection)?).unwrap() + "\n";
+
+    Ok(vec![OutputFile {
+      path: PathBuf::from(format!("{}.json", reflection.name)),
+      contents: output_file,
+    }])
+  }
+}
+
+fn define_value(value: &DefineValue) -> Value {
+  match value {
+    DefineValue::Bool(value) => Value::from(*value),
+    DefineValue::Integer(value) => Value::from(*value),
+    DefineValue::Float(value) => Value::from(*value),
+    DefineValue::Text(value) => Value::from(value.as_str()),
+  }
+}
+
+fn reflect_layout(layout: &[(String, Option<i64>)]) -> BTreeMap<&str, Option<i64>> {
+  layout
+    .iter()
+    .map(|(name, value)| (name.as_str(), *value))
+    .collect()
+}
+
+fn reflect_variable(variable: &TypedVariable) -> ReflectedVariable<'_> {
+  ReflectedVariable {
+    name: &variable.identifier,
+    type_label: common::glsl_type_name(&variable.type_label),
+    kind: variable.scalar_kind().map(|kind| kind.name()),
+    array_size: variable.array_size,
+    layout: reflect_layout(&variable.layout),
+  }
+}
+
+fn reflect_fields<'a>(
+  layout: &StructLayout,
+  fields: &'a [TypedVariable],
+) -> Vec<ReflectedField<'a>> {
+  layout
+    .fields
+    .iter()
+    .zip(fields)
+    .map(|(field, variable)| ReflectedField {
+      name: &variable.identifier,
+      type_label: common::glsl_type_name(&variable.type_label),
+      array_size: field.array_size,
+      offset: field.offset,
+      size: field.size,
+    })
+    .collect()
+}
@@ -0,0 +1,464 @@
+use super::common::{self, ShaderData, TypedVariable};
+use glsl::syntax::TypeSpecifierNonArray;
+use std::collections::BTreeSet;
+
+/// The type of the components of a GLSL type. Booleans take 4 bytes in buffers, like a `uint`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalarKind {
+  Float,
+  Int,
+  UInt,
+  Bool,
+}
+
+impl ScalarKind {
+  pub fn name(&self) -> &'static str {
+    match self {
+      ScalarKind::Float => "float",
+      ScalarKind::Int => "int",
+      ScalarKind::UInt => "uint",
+      ScalarKind::Bool => "bool",
+    }
+  }
+}
+
+/// The shape of a GLSL type. Scalars have one column and one row, vectors one column of `rows`
+/// components, and matrices `columns` columns of `rows` components.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldType {
+  Value {
+    kind: ScalarKind,
+    columns: usize,
+    rows: usize,
+  },
+  Struct(String),
+}
+
+/// A field of a struct or of a uniform block, placed with the std140 rules.
+#[derive(Clone, Debug)]
+pub struct FieldLayout {
+  pub name: String,
+  pub ty: FieldType,
+  pub array_size: Option<usize>,
+  pub offset: usize,
+  /// The size of the field, including the padding of the elements of an array.
+  pub size: usize,
+}
+
+/// A struct or a uniform block, placed with the std140 rules.
+#[derive(Clone, Debug)]
+pub struct StructLayout {
+  pub name: String,
+  pub fields: Vec<FieldLayout>,
+  pub size: usize,
+  pub align: usize,
+}
+
+/// A member of a struct, with the padding of std140 as members of its own.
+#[derive(Clone, Debug)]
+pub enum Member<'a> {
+  Field(&'a FieldLayout),
+  Padding(usize),
+}
+
+impl StructLayout {
+  /// Gets the fields of the struct and the bytes of padding before them and at its end.
+  pub fn members(&self) -> Vec<Member<'_>> {
+    let mut members = Vec::new();
+    let mut offset = 0;
+
+    for field in &self.fields {
+      if field.offset > offset {
+        members.push(Member::Padding(field.offset - offset));
+      }
+      members.push(Member::Field(field));
+      offset = field.offset + field.size;
+    }
+
+    if self.size > offset {
+      members.push(Member::Padding(self.size - offset));
+    }
+
+    members
+  }
+}
+
+impl FieldLayout {
+  /// Gets the dimensions of the field as nested arrays of scalars (or of structs), outermost
+  /// first. Every column of a matrix and every element of an array are padded to 4 components.
+  pub fn dimensions(&self) -> Vec<usize> {
+    let mut dimensions = Vec::new();
+    if let Some(length) = self.array_size {
+      dimensions.push(length);
+    }
+
+    match (&self.ty, self.array_size) {
+      (FieldType::Value { columns: 1, .. }, Some(_)) => dimensions.push(4),
+      (
+        FieldType::Value {
+          columns: 1,
+          rows: 1,
+          ..
+        },
+        None,
+      ) => {}
+      (
+        FieldType::Value {
+          columns: 1, rows, ..
+        },
+        None,
+      ) => dimensions.push(*rows),
+      (FieldType::Value { columns, .. }, _) => dimensions.extend([*columns, 4]),
+      (FieldType::Struct(_), _) => {}
+    }
+
+    dimensions
+  }
+}
+
+/// Gets the shape of a type, or `None` for opaque types such as samplers.
+pub fn field_type(ty: &TypeSpecifierNonArray) -> Option<FieldType> {
+  use ScalarKind::*;
+  use TypeSpecifierNonArray as T;
+
+  let (kind, columns, rows) = match ty {
+    T::Float => (Float, 1, 1),
+    T::Vec2 => (Float, 1, 2),
+    T::Vec3 => (Float, 1, 3),
+    T::Vec4 => (Float, 1, 4),
+    T::Int => (Int, 1, 1),
+    T::IVec2 => (Int, 1, 2),
+    T::IVec3 => (Int, 1, 3),
+    T::IVec4 => (Int, 1, 4),
+    T::UInt => (UInt, 1, 1),
+    T::UVec2 => (UInt, 1, 2),
+    T::UVec3 => (UInt, 1, 3),
+    T::UVec4 => (UInt, 1, 4),
+    T::Bool => (Bool, 1, 1),
+    T::BVec2 => (Bool, 1, 2),
+    T::BVec3 => (Bool, 1, 3),
+    T::BVec4 => (Bool, 1, 4),
+    T::Mat2 => (Float, 2, 2),
+    T::Mat3 => (Float, 3, 3),
+    T::Mat4 => (Float, 4, 4),
+    T::Mat23 => (Float, 2, 3),
+    T::Mat24 => (Float, 2, 4),
+    T::Mat32 => (Float, 3, 2),
+    T::Mat34 => (Float, 3, 4),
+    T::Mat42 => (Float, 4, 2),
+    T::Mat43 => (Float, 4, 3),
+    T::TypeName(name) => return Some(FieldType::Struct(name.to_string())),
+    _ => return None,
+  };
+
+  Some(FieldType::Value {
+    kind,
+    columns,
+    rows,
+  })
+}
+
+fn round_up(value: usize, align: usize) -> usize {
+  value.div_ceil(align) * align
+}
+
+/// Gets the std140 size and alignment of a single value of a type.
+fn size_and_align(ty: &FieldType, structs: &[StructLayout]) -> Result<(usize, usize), String> {
+  match ty {
+    // Every column of a matrix is aligned like a vec4
+    FieldType::Value { columns, .. } if *columns > 1 => Ok((16 * columns, 16)),
+    FieldType::Value { rows: 1, .. } => Ok((4, 4)),
+    FieldType::Value { rows: 2, .. } => Ok((8, 8)),
+    FieldType::Value { rows, .. } => Ok((4 * rows, 16)),
+    FieldType::Struct(name) => structs
+      .iter()
+      .find(|layout| &layout.name == name)
+      .map(|layout| (layout.size, layout.align))
+      .ok_or_else(|| format!("unknown struct {}", name)),
+  }
+}
+
+/// Places the fields of a struct or of a uniform block with the std140 rules. `structs` are the
+/// layouts of the structs declared before it.
+pub fn struct_layout(
+  name: &str,
+  fields: &[TypedVariable],
+  structs: &[StructLayout],
+) -> Result<StructLayout, String> {
+  let mut layouts = Vec::new();
+  let mut offset = 0;
+  let mut struct_align = 16;
+
+  for field in fields {
+    let ty = field_type(&field.type_label).ok_or_else(|| {
+      format!(
+        "the field {} of {} has an opaque type",
+        field.identifier, name
+      )
+    })?;
+    let (size, align) = size_and_align(&ty, structs)?;
+
+    if field.is_array && field.array_size.is_none() {
+      return Err(format!(
+        "the size of the array {} of {} is not known",
+        field.identifier, name
+      ));
+    }
+
+    // The elements of an array are aligned like a vec4
+    let (size, align) = match field.array_size {
+      Some(length) => (round_up(size, 16) * length, 16),
+      None => (size, align),
+    };
+
+    offset = round_up(offset, align);
+    struct_align = struct_align.max(align);
+    layouts.push(FieldLayout {
+      name: field.identifier.clone(),
+      ty,
+      array_size: field.array_size,
+      offset,
+      size,
+    });
+    offset += size;
+  }
+
+  Ok(StructLayout {
+    name: name.to_string(),
+    fields: layouts,
+    size: round_up(offset, struct_align),
+    align: struct_align,
+  })
+}
+
+/// The layouts of the uniform blocks of a shader and of the structs they use.
+#[derive(Clone, Debug)]
+pub struct ShaderLayouts {
+  pub structs: Vec<StructLayout>,
+  pub blocks: Vec<StructLayout>,
+}
+
+impl ShaderLayouts {
+  /// Gets the structs and then the blocks, so that every struct comes before its uses.
+  pub fn all(&self) -> impl Iterator<Item = &StructLayout> {
+    self.structs.iter().chain(&self.blocks)
+  }
+}
+
+/// Gets the names of the structs used by the uniform blocks of a shader, directly or through
+/// other structs.
+fn block_structs(shader_data: &ShaderData) -> BTreeSet<String> {
+  let mut used = BTreeSet::new();
+  let mut pending = shader_data
+    .blocks
+    .iter()
+    .map(|block| &block.fields)
+    .collect::<Vec<&Vec<TypedVariable>>>();
+
+  while let Some(fields) = pending.pop() {
+    for field in fields {
+      if let Some(FieldType::Struct(name)) = field_type(&field.type_label) {
+        if let Some(definition) = shader_data
+          .structs
+          .iter()
+          .find(|definition| definition.name == name)
+        {
+          if used.insert(name) {
+            pending.push(&definition.fields);
+          }
+        }
+      }
+    }
+  }
+
+  used
+}
+
+/// Places the uniform blocks of a shader and the structs they use. Other structs are left out,
+/// as they can have samplers or other types that can not be in a buffer. Arrays whose size is
+/// not known are an error, as the size of the block would not be either. So are blocks that are
+/// not std140, whose layout is only known once the program is linked.
+pub fn shader_layouts(shader_data: &ShaderData) -> Result<ShaderLayouts, String> {
+  for block in &shader_data.blocks {
+    let packing = common::block_packing(&block.layout);
+    if packing != "std140" {
+      return Err(format!(
+        "the uniform block {} is {}, whose layout is only known once the program is linked. Declare it with layout(std140)",
+        block.name, packing
+      ));
+    }
+
+    // The matrices are laid out column by column
+    let is_row_major = block
+      .layout
+      .iter()
+      .chain(block.fields.iter().flat_map(|field| &field.layout))
+      .any(|(name, _)| name == "row_major");
+    if is_row_major {
+      return Err(format!(
+        "the uniform block {} has row_major matrices, which are not supported",
+        block.name
+      ));
+    }
+  }
+
+  let used = block_structs(shader_data);
+  let mut structs: Vec<StructLayout> = Vec::new();
+
+  // Structs are declared before they are used, so every struct only needs the ones before it
+  for definition in &shader_data.structs {
+    if used.contains(&definition.name) {
+      structs.push(struct_layout(
+        &definition.name,
+        &definition.fields,
+        &structs,
+      )?);
+    }
+  }
+
+  let blocks = shader_data
+    .blocks
+    .iter()
+    .map(|block| struct_layout(&block.name, &block.fields, &structs))
+    .collect::<Result<Vec<StructLayout>, String>>()?;
+
+  Ok(ShaderLayouts { structs, blocks })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{shader_layouts, ShaderLayouts, StructLayout};
+  use crate::generator::common::extract_shader_data;
+  use crate::utils::get_shader_type::ShaderType;
+
+  fn layouts(source: &str) -> Result<ShaderLayouts, String> {
+    shader_layouts(&extract_shader_data(
+      &source.to_string(),
+      ShaderType::Fragment,
+    ))
+  }
+
+  fn offsets(layout: &StructLayout) -> Vec<(&str, usize, usize)> {
+    layout
+      .fields
+      .iter()
+      .map(|field| (field.name.as_str(), field.offset, field.size))
+      .collect()
+  }
+
+  #[test]
+  fn places_the_fields_with_std140() {
+    let layouts = layouts(
+      "#version 300 es
+precision highp float;
+layout(std140) uniform Scene {
+  vec3 ambient;
+  float exposure;
+  mat3 normalMatrix;
+  float weights[3];
+  vec2 jitter;
+  vec3 fogColor;
+  mat2x3 skew;
+};
+void main() {}
+",
+    )
+    .unwrap();
+    let scene = &layouts.blocks[0];
+
+    assert_eq!(
+      offsets(scene),
+      [
+        // A vec3 is aligned like a vec4, but a float fits after it
+        ("ambient", 0, 12),
+        ("exposure", 12, 4),
+        // Every column of a matrix takes a vec4
+        ("normalMatrix", 16, 48),
+        // And so does every element of an array
+        ("weights", 64, 48),
+        ("jitter", 112, 8),
+        ("fogColor", 128, 12),
+        ("skew", 144, 32),
+      ]
+    );
+    assert_eq!(scene.size, 176);
+  }
+
+  #[test]
+  fn places_the_structs_used_by_the_blocks() {
+    let layouts = layouts(
+      "#version 300 es
+precision highp float;
+struct Material { sampler2D albedo; };
+struct Light { vec3 position; float intensity; };
+struct Lights { Light items[2]; int count; };
+layout(std140) uniform Scene {
+  float time;
+  Lights lights;
+};
+void main() {}
+",
+    )
+    .unwrap();
+
+    let names = layouts
+      .structs
+      .iter()
+      .map(|layout| layout.name.as_str())
+      .collect::<Vec<&str>>();
+    assert_eq!(names, ["Light", "Lights"]);
+
+    assert_eq!(layouts.structs[0].size, 16);
+    assert_eq!(
+      offsets(&layouts.structs[1]),
+      [("items", 0, 32), ("count", 32, 4)]
+    );
+    assert_eq!(layouts.structs[1].size, 48);
+
+    // Structs are aligned like a vec4
+    assert_eq!(
+      offsets(&layouts.blocks[0]),
+      [("time", 0, 4), ("lights", 16, 48)]
+    );
+    assert_eq!(layouts.blocks[0].size, 64);
+  }
+
+  #[test]
+  fn blocks_inherit_the_default_layout() {
+    let source = "#version 300 es
+precision highp float;
+layout(std140) uniform;
+uniform Scene { vec4 color; };
+void main() {}
+";
+
+    assert!(layouts(source).is_ok());
+  }
+
+  #[test]
+  fn rejects_blocks_that_are_not_std140() {
+    for layout in ["", "layout(shared) ", "layout(packed) "] {
+      let source = format!(
+        "#version 300 es
+precision highp float;
+{}uniform Scene {{ vec4 color; }};
+void main() {{}}
+",
+        layout
+      );
+
+      assert!(layouts(&source).is_err(), "{}", layout);
+    }
+  }
+
+  #[test]
+  fn rejects_row_major_matrices() {
+    let source = "#version 300 es
+precision highp float;
+layout(std140, row_major) uniform Scene { mat4 model; };
+void main() {}
+";
+
+    assert!(layouts(source).is_err());
+  }
+}
@@ -1,2 +1,120 @@
 pub mod common;
+pub mod cpp;
+pub mod index;
+pub mod json;
+pub mod layout;
+pub mod naming;
+pub mod rust;
+pub mod template;
 pub mod type_script;
+pub mod vertex_layout;
+
+use crate::config::Settings;
+use crate::utils::get_shader_type::ShaderType;
+use common::{ProcessedShader, ShaderData, ShaderVariant};
+use std::path::PathBuf;
+
+/// A shader once its imports are resolved, every transform ran and its interface is reflected.
+#[derive(Clone, Debug)]
+pub struct Reflection {
+  /// The name the outputs are named after, e.g. `blur_frag` for `blur.frag`.
+  pub name: String,
+  /// The name the shader is exported as in the configured naming convention, e.g. `BlurFrag`.
+  pub export_name: String,
+  pub stage: ShaderType,
+  pub shader: ProcessedShader,
+  pub data: ShaderData,
+  pub variants: Vec<ShaderVariant>,
+}
+
+/// A file produced by a generator. `path` is relative to the folder of the shader in the output
+/// folder, which mirrors the input folder.
+#[derive(Clone, Debug)]
+pub struct OutputFile {
+  pub path: PathBuf,
+  pub contents: String,
+}
+
+/// Turns the reflection of a shader into output files, e.g. TypeScript types or a C++ header.
+pub trait Generator {
+  /// The name the generator is selected with in `--format`.
+  fn name(&self) -> &str;
+
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String>;
+}
+
+/// The generators that can be selected by name. The default one has the built-in generators,
+/// and more can be registered on top of them.
+pub struct Registry {
+  generators: Vec<Box<dyn Generator>>,
+}
+
+impl Registry {
+  pub fn new() -> Self {
+    Registry {
+      generators: Vec::new(),
+    }
+  }
+
+  /// Adds a generator, replacing the one with the same name.
+  pub fn register(&mut self, generator: Box<dyn Generator>) {
+    self
+      .generators
+      .retain(|other| other.name() != generator.name());
+    self.generators.push(generator);
+  }
+
+  pub fn get(&self, name: &str) -> Option<&dyn Generator> {
+    self
+      .generators
+      .iter()
+      .find(|generator| generator.name() == name)
+      .map(|generator| generator.as_ref())
+  }
+
+  pub fn names(&self) -> Vec<&str> {
+    self
+      .generators
+      .iter()
+      .map(|generator| generator.name())
+      .collect()
+  }
+}
+
+impl Default for Registry {
+  fn default() -> Self {
+    let mut registry = Registry::new();
+    registry.register(Box::new(type_script::TypeScriptGenerator));
+    registry.register(Box::new(rust::RustGenerator));
+    registry.register(Box::new(cpp::CppGenerator));
+    registry.register(Box::new(json::JsonGenerator));
+    registry.register(Box::new(template::TemplateGenerator));
+    registry
+  }
+}
+
+/// Reflects a shader without running the import resolver or the transforms, for the tests of the
+/// generators.
+#[cfg(test)]
+pub(crate) fn test_reflection(name: &str, source: &str, stage: ShaderType) -> Reflection {
+  let content = source.to_string();
+
+  Reflection {
+    name: name.to_string(),
+    export_name: naming::to_case(name, crate::config::NamingConvention::Pascal),
+    stage,
+    data: common::extract_shader_data(&content, stage),
+    shader: ProcessedShader {
+      defines: Vec::new(),
+      includes: Vec::new(),
+      source: content.clone(),
+      content,
+      names: std::collections::BTreeMap::new(),
+    },
+    variants: Vec::new(),
+  }
+}
@@ -0,0 +1,206 @@
+use crate::config::{NamingConvention, Settings};
+use std::path::Path;
+
+/// Names that an export can not have: the reserved words of JavaScript and TypeScript, and the
+/// names declared by the generated modules.
+const RESERVED_WORDS: [&str; 55] = [
+  "await",
+  "break",
+  "case",
+  "catch",
+  "class",
+  "const",
+  "continue",
+  "debugger",
+  "default",
+  "delete",
+  "do",
+  "else",
+  "enum",
+  "export",
+  "extends",
+  "false",
+  "finally",
+  "for",
+  "function",
+  "if",
+  "implements",
+  "import",
+  "in",
+  "instanceof",
+  "interface",
+  "let",
+  "new",
+  "null",
+  "package",
+  "private",
+  "protected",
+  "public",
+  "return",
+  "static",
+  "super",
+  "switch",
+  "this",
+  "throw",
+  "true",
+  "try",
+  "typeof",
+  "var",
+  "void",
+  "while",
+  "with",
+  "yield",
+  "SOURCE",
+  "VARIANTS",
+  "getVariant",
+  "SHADERS",
+  "VERTEX_LAYOUT",
+  "Vertex",
+  "bindAttributeLocations",
+  "setupAttributes",
+  "packVertices",
+];
+
+pub fn capitalize_first_letter(s: &str) -> String {
+  let mut chars = s.chars();
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => String::new(),
+  }
+}
+
+pub fn lowercase_first_letter(s: &str) -> String {
+  let mut chars = s.chars();
+  match chars.next() {
+    Some(first) => first.to_lowercase().chain(chars).collect(),
+    None => String::new(),
+  }
+}
+
+/// Converts a name to a naming convention, e.g. `my-shader` to `MyShader` or `myShader`. Every
+/// character that can not be in an identifier separates two words.
+pub fn to_case(name: &str, naming: NamingConvention) -> String {
+  let name = name
+    .split(|c: char| !c.is_alphanumeric())
+    .map(capitalize_first_letter)
+    .collect::<String>();
+
+  match naming {
+    NamingConvention::Pascal => name,
+    NamingConvention::Camel => lowercase_first_letter(&name),
+  }
+}
+
+/// Checks if a name is a reserved word or is declared by the generated modules, which also
+/// declare the variants as `VARIANT_0`, `VARIANT_1`... and their sources as `VARIANT_0_SOURCE`,
+/// `VARIANT_1_SOURCE`...
+pub fn is_reserved(name: &str) -> bool {
+  let is_variant = name
+    .strip_prefix("VARIANT_")
+    .map(|name| name.strip_suffix("_SOURCE").unwrap_or(name))
+    .is_some_and(|index| !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()));
+
+  RESERVED_WORDS.contains(&name) || is_variant
+}
+
+/// Converts the parts of a name to an identifier in a naming convention. Names that do not
+/// start with a letter, such as `2d_blit`, and reserved words get a `_` prefix.
+pub fn identifier(parts: &[&str], naming: NamingConvention) -> String {
+  let name = to_case(&parts.join(" "), naming);
+
+  if !name.starts_with(char::is_alphabetic) || is_reserved(&name) {
+    format!("_{}", name)
+  } else {
+    name
+  }
+}
+
+/// Gets the name of the outputs of a shader file. With `stage_suffix` its extension is a suffix
+/// of the name, e.g. `blur_frag` for `blur.frag`.
+pub fn output_name(file_path: &Path, settings: &Settings) -> String {
+  let stem = file_path.file_stem().unwrap().to_str().unwrap();
+
+  match file_path
+    .extension()
+    .and_then(|extension| extension.to_str())
+  {
+    Some(extension) if settings.stage_suffix() => format!("{}_{}", stem, extension),
+    _ => stem.to_string(),
+  }
+}
+
+/// Gets the name a shader file is exported as, e.g. `BlurFrag` for `blur.frag`, with the
+/// configured prefix and suffix.
+pub fn export_name(file_path: &Path, settings: &Settings) -> String {
+  let stem = file_path.file_stem().unwrap().to_str().unwrap();
+  let mut parts = vec![settings.export_prefix(), stem];
+
+  if let Some(extension) = file_path
+    .extension()
+    .and_then(|extension| extension.to_str())
+  {
+    if settings.stage_suffix() {
+      parts.push(extension);
+    }
+  }
+
+  parts.push(settings.export_suffix());
+  identifier(&parts, settings.naming())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{identifier, is_reserved, to_case};
+  use crate::config::NamingConvention;
+
+  #[test]
+  fn converts_to_the_naming_convention() {
+    assert_eq!(
+      to_case("my-shader vert", NamingConvention::Pascal),
+      "MyShaderVert"
+    );
+    assert_eq!(
+      to_case("my_shader.vert", NamingConvention::Camel),
+      "myShaderVert"
+    );
+  }
+
+  #[test]
+  fn names_that_do_not_start_with_a_letter_get_a_prefix() {
+    assert_eq!(
+      identifier(&["2d_blit"], NamingConvention::Pascal),
+      "_2dBlit"
+    );
+    assert_eq!(
+      identifier(&["2d_blit", "vert"], NamingConvention::Camel),
+      "_2dBlitVert"
+    );
+  }
+
+  #[test]
+  fn reserved_words_get_a_prefix() {
+    assert_eq!(
+      identifier(&["default"], NamingConvention::Camel),
+      "_default"
+    );
+    assert_eq!(
+      identifier(&["get", "variant"], NamingConvention::Camel),
+      "_getVariant"
+    );
+    assert_eq!(
+      identifier(&["default"], NamingConvention::Pascal),
+      "Default"
+    );
+  }
+
+  #[test]
+  fn the_variants_and_their_sources_are_reserved() {
+    assert!(is_reserved("SOURCE"));
+    assert!(is_reserved("VARIANT_12_SOURCE"));
+    assert!(is_reserved("VARIANT_0"));
+    assert!(!is_reserved("VARIANT_"));
+    assert!(!is_reserved("VARIANT_0_COLOR"));
+    assert!(!is_reserved("VARIANT__SOURCE"));
+    assert!(!is_reserved("VARIANT_A_SOURCE"));
+  }
+}
@@ -0,0 +1,519 @@
+use super::common::{self, ShaderData, TypedVariable};
+use super::layout::{self, FieldType, Member, ScalarKind, ShaderLayouts, StructLayout};
+use super::{Generator, OutputFile, Reflection};
+use crate::config::Settings;
+use crate::utils::get_shader_type::ShaderType;
+use glsl::syntax::TypeSpecifierNonArray;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The keywords of Rust, which can only be used as names with the `r#` prefix.
+const KEYWORDS: [&str; 48] = [
+  "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+  "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+  "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+  "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+  "virtual", "where", "while", "yield",
+];
+
+/// The items every generated module declares, which the structs, blocks and constants of the
+/// shader can not be named after.
+const ITEMS: [&str; 7] = [
+  "AttributeKind",
+  "VertexAttribute",
+  "UniformValue",
+  "UniformSetter",
+  "SOURCE",
+  "ATTRIBUTES",
+  "Uniforms",
+];
+
+/// The types shared by the items of every generated file.
+const PRELUDE: &str = r#"/// The type of the components of a vertex attribute. Integer attributes are not converted to
+/// floats, and are bound with `glVertexAttribIPointer` or a `Sint32`/`Uint32` vertex format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeKind {
+  Float,
+  Int,
+  UInt,
+}
+
+/// An input of the vertex shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+  pub name: &'static str,
+  /// The `layout(location = N)` of the input, if the shader sets it.
+  pub location: Option<u32>,
+  pub kind: AttributeKind,
+  /// The number of components of a column, e.g. 3 for a `vec3` or a `mat3`.
+  pub components: u32,
+  /// The number of locations it takes, e.g. 3 for a `mat3`.
+  pub slots: u32,
+  pub glsl_type: &'static str,
+}
+
+/// The value of a uniform, as the components of its elements.
+#[derive(Clone, Copy, Debug)]
+pub enum UniformValue<'a> {
+  Float { components: usize, values: &'a [f32] },
+  Int { components: usize, values: &'a [i32] },
+  UInt { components: usize, values: &'a [u32] },
+  Matrix { columns: usize, rows: usize, values: &'a [f32] },
+}
+
+/// Sets the uniforms of a program, e.g. with `glUniform*` or the uniforms of a render pass.
+pub trait UniformSetter {
+  fn set_uniform(&mut self, name: &str, value: UniformValue);
+}
+"#;
+
+pub struct RustGenerator;
+
+impl Generator for RustGenerator {
+  fn name(&self) -> &str {
+    "rust"
+  }
+
+  /// Generates a Rust module for a shader: its source, its constants, `#[repr(C)]` structs for its
+  /// uniform blocks, the descriptors of its vertex attributes and a struct of its uniforms.
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    _settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let shader = &reflection.shader;
+    let shader_data = &reflection.data;
+    let layouts = layout::shader_layouts(shader_data)
+      .map_err(|error| format!("could not lay out the uniform blocks: {}", error))?;
+    check_names(shader_data, &layouts)?;
+
+    let mut output_file = String::new();
+    output_file.push_str("// DO NOT EDIT THIS FILE\n");
+    output_file.push_str("// This file is generated by glsl-types\n");
+    output_file.push_str(
+      "// It needs bytemuck = { version = \"1\", features = [\"derive\", \"min_const_generics\"] }\n\n",
+    );
+    output_file.push_str(PRELUDE);
+
+    output_file.push_str(&format!(
+      "\npub const SOURCE: &str = {};\n",
+      raw_string(&shader.source)
+    ));
+
+    for constant in &shader_data.constants {
+      let label = convert_glsl_to_rust_label(&constant.type_label).ok_or_else(|| {
+        format!(
+          "the constant {} is a {}, which has no Rust type",
+          constant.identifier,
+          common::glsl_type_name(&constant.type_label)
+        )
+      })?;
+
+      output_file.push_str(&format!(
+        "pub const {}: {} = {};\n",
+        identifier(&constant.identifier),
+        label,
+        format_constant_value(&constant.type_label, &constant.value)
+      ));
+    }
+
+    for layout in layouts.all() {
+      output_file.push_str(&format_struct(layout));
+    }
+
+    if shader_data.shader_type == ShaderType::Vertex {
+      output_file.push_str(&format_attributes(&shader_data.ins));
+    }
+
+    output_file.push_str(&format_uniforms(&shader_data.uniforms, &shader.names));
+
+    Ok(vec![OutputFile {
+      path: PathBuf::from(format!("{}.rs", reflection.name)),
+      contents: output_file,
+    }])
+  }
+}
+
+/// Checks that no struct, block or constant of the shader is named like an item of the module.
+fn check_names(shader_data: &ShaderData, layouts: &ShaderLayouts) -> Result<(), String> {
+  let names = layouts
+    .structs
+    .iter()
+    .map(|layout| ("struct", &layout.name))
+    .chain(
+      layouts
+        .blocks
+        .iter()
+        .map(|layout| ("uniform block", &layout.name)),
+    )
+    .chain(
+      shader_data
+        .constants
+        .iter()
+        .map(|constant| ("constant", &constant.identifier)),
+    );
+
+  for (kind, name) in names {
+    if ITEMS.contains(&name.as_str()) {
+      return Err(format!(
+        "the {} {} has the name of an item of the generated module, rename it",
+        kind, name
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+/// Formats a raw string literal with enough `#` for the source to not end it.
+fn raw_string(source: &str) -> String {
+  let mut hashes = String::from("#");
+  while source.contains(&format!("\"{}", hashes)) {
+    hashes.push('#');
+  }
+
+  format!("r{}\"{}\"{}", hashes, source, hashes)
+}
+
+/// Escapes a name that is a keyword of Rust, as `r#type` for `type`. `crate`, `self`, `Self` and
+/// `super` can not be raw identifiers, and get a `_` suffix instead.
+fn identifier(name: &str) -> String {
+  match name {
+    "crate" | "self" | "Self" | "super" => format!("{}_", name),
+    _ if KEYWORDS.contains(&name) => format!("r#{}", name),
+    _ => name.to_string(),
+  }
+}
+
+pub fn to_snake_case(name: &str) -> String {
+  let mut output = String::new();
+  for (i, c) in name.chars().enumerate() {
+    if c.is_ascii_uppercase() {
+      if i > 0 && !output.ends_with('_') {
+        output.push('_');
+      }
+      output.push(c.to_ascii_lowercase());
+    } else {
+      output.push(c);
+    }
+  }
+
+  output
+}
+
+fn scalar_label(kind: ScalarKind) -> &'static str {
+  match kind {
+    ScalarKind::Float => "f32",
+    // Booleans are 4 bytes in buffers and are set with `glUniform*i`
+    ScalarKind::Int | ScalarKind::Bool => "i32",
+    ScalarKind::UInt => "u32",
+  }
+}
+
+/// Formats a single value of a type, as it is passed to `glUniform*`.
+pub fn value_label(ty: &FieldType) -> String {
+  match ty {
+    FieldType::Value {
+      kind,
+      columns: 1,
+      rows: 1,
+    } => scalar_label(*kind).to_string(),
+    FieldType::Value {
+      kind,
+      columns: 1,
+      rows,
+    } => format!("[{}; {}]", scalar_label(*kind), rows),
+    FieldType::Value {
+      kind,
+      columns,
+      rows,
+    } => format!("[[{}; {}]; {}]", scalar_label(*kind), rows, columns),
+    FieldType::Struct(name) => name.clone(),
+  }
+}
+
+/// Formats a uniform block or a struct as a `#[repr(C)]` struct with the padding of std140 as
+/// explicit fields, so that it can be cast to bytes with `bytemuck`.
+fn format_struct(layout: &StructLayout) -> String {
+  let mut fields = Vec::new();
+  let mut assertions = Vec::new();
+
+  for (i, member) in layout.members().iter().enumerate() {
+    let field = match member {
+      Member::Field(field) => field,
+      Member::Padding(size) => {
+        fields.push(format!("  pub _pad{}: [u8; {}],", i, size));
+        continue;
+      }
+    };
+
+    let element = match &field.ty {
+      FieldType::Value { kind, .. } => scalar_label(*kind).to_string(),
+      FieldType::Struct(name) => name.clone(),
+    };
+    let label = field
+      .dimensions()
+      .iter()
+      .rev()
+      .fold(element, |label, length| format!("[{}; {}]", label, length));
+
+    let name = identifier(&to_snake_case(&field.name));
+    fields.push(format!("  pub {}: {},", name, label));
+    assertions.push(format!(
+      "const _: () = assert!(std::mem::offset_of!({}, {}) == {});",
+      layout.name, name, field.offset
+    ));
+  }
+
+  assertions.push(format!(
+    "const _: () = assert!(std::mem::size_of::<{}>() == {});",
+    layout.name, layout.size
+  ));
+
+  format!(
+    r#"
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct {} {{
+{}
+}}
+
+{}
+"#,
+    layout.name,
+    fields.join("\n"),
+    assertions.join("\n")
+  )
+}
+
+/// Formats the inputs of a vertex shader as a list of `VertexAttribute`.
+fn format_attributes(ins: &[TypedVariable]) -> String {
+  let attributes = ins
+    .iter()
+    .filter_map(|input| {
+      let (kind, columns, rows) = match layout::field_type(&input.type_label)? {
+        FieldType::Value {
+          kind,
+          columns,
+          rows,
+        } => (kind, columns, rows),
+        FieldType::Struct(_) => return None,
+      };
+      let kind = match kind {
+        ScalarKind::Float => "Float",
+        ScalarKind::Int | ScalarKind::Bool => "Int",
+        ScalarKind::UInt => "UInt",
+      };
+      let location = match input.location {
+        Some(location) => format!("Some({})", location),
+        None => "None".to_string(),
+      };
+
+      Some(format!(
+        r#"  VertexAttribute {{
+    name: "{}",
+    location: {},
+    kind: AttributeKind::{},
+    components: {},
+    slots: {},
+    glsl_type: "{}",
+  }},"#,
+        input.identifier,
+        location,
+        kind,
+        rows,
+        columns * input.array_size.unwrap_or(1),
+        common::glsl_type_name(&input.type_label)
+      ))
+    })
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  format!(
+    "\npub const ATTRIBUTES: &[VertexAttribute] = &[\n{}\n];\n",
+    attributes
+  )
+}
+
+/// Formats the uniforms outside of blocks as a struct with a method that sets all of them.
+/// Samplers are set to their texture unit. Uniforms of a struct type are left out, as they are
+/// set field by field.
+fn format_uniforms(uniforms: &[TypedVariable], names: &BTreeMap<String, String>) -> String {
+  let mut fields = Vec::new();
+  let mut setters = Vec::new();
+
+  for uniform in uniforms {
+    let ty = match layout::field_type(&uniform.type_label) {
+      Some(FieldType::Struct(_)) => continue,
+      Some(ty) => ty,
+      None => FieldType::Value {
+        kind: ScalarKind::Int,
+        columns: 1,
+        rows: 1,
+      },
+    };
+
+    if uniform.is_array && uniform.array_size.is_none() {
+      continue;
+    }
+
+    let label = match uniform.array_size {
+      Some(length) => format!("[{}; {}]", value_label(&ty), length),
+      None => value_label(&ty),
+    };
+    let values = match uniform.array_size {
+      Some(_) => "&self.{}",
+      None => "std::slice::from_ref(&self.{})",
+    };
+    let field_name = identifier(&to_snake_case(&uniform.identifier));
+    let values = format!(
+      "bytemuck::cast_slice({})",
+      values.replace("{}", &field_name)
+    );
+
+    let value = match ty {
+      FieldType::Value {
+        columns: 1,
+        rows,
+        kind,
+      } => {
+        let variant = match kind {
+          ScalarKind::Float => "Float",
+          ScalarKind::Int | ScalarKind::Bool => "Int",
+          ScalarKind::UInt => "UInt",
+        };
+        format!(
+          "UniformValue::{} {{ components: {}, values: {} }}",
+          variant, rows, values
+        )
+      }
+      FieldType::Value { columns, rows, .. } => format!(
+        "UniformValue::Matrix {{ columns: {}, rows: {}, values: {} }}",
+        columns, rows, values
+      ),
+      FieldType::Struct(_) => unreachable!(),
+    };
+
+    // The name in the source, which is different when the names are mangled
+    let name = names
+      .get(&uniform.identifier)
+      .unwrap_or(&uniform.identifier);
+
+    fields.push(format!("  pub {}: {},", field_name, label));
+    setters.push(format!("    setter.set_uniform(\"{}\", {});", name, value));
+  }
+
+  format!(
+    r#"
+#[derive(Clone, Copy, Debug)]
+pub struct Uniforms {{
+{}
+}}
+
+impl Uniforms {{
+  pub fn apply(&self, setter: &mut impl UniformSetter) {{
+{}
+  }}
+}}
+"#,
+    fields.join("\n"),
+    setters.join("\n")
+  )
+}
+
+fn format_constant_value(type_label: &TypeSpecifierNonArray, value: &str) -> String {
+  match type_label {
+    TypeSpecifierNonArray::Float | TypeSpecifierNonArray::Double => common::float_literal(value),
+    _ => value.to_string(),
+  }
+}
+
+/// Gets the Rust type of a constant, or `None` for the types constants can not have.
+fn convert_glsl_to_rust_label(ty: &TypeSpecifierNonArray) -> Option<&'static str> {
+  match ty {
+    TypeSpecifierNonArray::Float => Some("f32"),
+    TypeSpecifierNonArray::Double => Some("f64"),
+    TypeSpecifierNonArray::Int => Some("i32"),
+    TypeSpecifierNonArray::UInt => Some("u32"),
+    TypeSpecifierNonArray::Bool => Some("bool"),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::RustGenerator;
+  use crate::config::Settings;
+  use crate::generator::{test_reflection, Generator};
+  use crate::utils::get_shader_type::ShaderType;
+
+  fn generate(source: &str) -> Result<String, String> {
+    let reflection = test_reflection("lit_frag", source, ShaderType::Fragment);
+    let files = RustGenerator.generate(&reflection, &Settings::default())?;
+
+    Ok(files[0].contents.clone())
+  }
+
+  #[test]
+  fn escapes_keywords() {
+    let output = generate(
+      "#version 300 es
+precision highp float;
+#define type 2
+layout(std140) uniform Scene { vec4 self; float match; };
+void main() {}
+",
+    )
+    .unwrap();
+
+    assert!(output.contains("pub const r#type: i32 = 2;"), "{}", output);
+    assert!(output.contains("pub self_: [f32; 4],"), "{}", output);
+    assert!(output.contains("pub r#match: f32,"), "{}", output);
+  }
+
+  #[test]
+  fn rejects_a_block_named_like_an_item_of_the_module() {
+    let error = generate(
+      "#version 300 es
+precision highp float;
+layout(std140) uniform Uniforms { vec4 color; };
+void main() {}
+",
+    )
+    .unwrap_err();
+
+    assert!(error.contains("uniform block Uniforms"), "{}", error);
+  }
+
+  #[test]
+  fn rejects_structs_and_constants_named_like_items_of_the_module() {
+    let struct_error = generate(
+      "#version 300 es
+precision highp float;
+struct VertexAttribute { vec4 color; };
+layout(std140) uniform Scene { VertexAttribute attribute; };
+void main() {}
+",
+    )
+    .unwrap_err();
+    assert!(
+      struct_error.contains("struct VertexAttribute"),
+      "{}",
+      struct_error
+    );
+
+    let constant_error = generate(
+      "#version 300 es
+precision highp float;
+#define SOURCE 1
+void main() {}
+",
+    )
+    .unwrap_err();
+    assert!(
+      constant_error.contains("constant SOURCE"),
+      "{}",
+      constant_error
+    );
+  }
+}
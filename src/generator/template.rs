@@ -0,0 +1,176 @@
+use super::json::{self, JsonReflection};
+use super::layout::{self, FieldType};
+use super::naming;
+use super::rust::{to_snake_case, value_label};
+use super::type_script::value_type;
+use super::{Generator, OutputFile, Reflection};
+use crate::config::{NamingConvention, Settings};
+use crate::{file_exists, read_file};
+use glsl::parser::Parse;
+use glsl::syntax::TypeSpecifierNonArray;
+use minijinja::{Environment, Error, ErrorKind};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The extensions of template files, removed from the name of the template to get the extension
+/// of the output, e.g. `kotlin.kt.jinja` renders `<name>.kt`.
+const TEMPLATE_EXTENSIONS: [&str; 3] = ["jinja", "j2", "tmpl"];
+
+/// What templates are rendered with: the JSON reflection, with the name of the shader file and
+/// the name of its export.
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+  #[serde(flatten)]
+  reflection: JsonReflection<'a>,
+  file: String,
+  type_name: String,
+}
+
+pub struct TemplateGenerator;
+
+impl Generator for TemplateGenerator {
+  fn name(&self) -> &str {
+    "template"
+  }
+
+  /// Renders the template of the settings with the reflection of a shader.
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let template_path = settings
+      .template()
+      .ok_or("no template, set `template` in the config file or pass --template")?;
+    let extension = output_extension(template_path).ok_or_else(|| {
+      format!(
+        "could not get the output extension from the template {}, name it like `types.ts.jinja`",
+        template_path.display()
+      )
+    })?;
+
+    if !file_exists(template_path.to_str().unwrap()) {
+      return Err(format!("template not found: {}", template_path.display()));
+    }
+
+    let mut environment = Environment::new();
+    add_filters(&mut environment);
+    let template = read_file(template_path.to_str().unwrap().to_string());
+    environment
+      .add_template("template", &template)
+      .map_err(|error| error.to_string())?;
+
+    let context = TemplateContext {
+      reflection: json::reflect(reflection)?,
+      file: format!("{}.{}", reflection.name, extension),
+      type_name: reflection.export_name.clone(),
+    };
+
+    let contents = environment
+      .get_template("template")
+      .and_then(|template| template.render(&context))
+      .map_err(|error| error.to_string())?;
+
+    Ok(vec![OutputFile {
+      path: PathBuf::from(&context.file),
+      contents,
+    }])
+  }
+}
+
+fn output_extension(template_path: &Path) -> Option<String> {
+  let mut name = template_path.file_name()?.to_str()?;
+  for extension in TEMPLATE_EXTENSIONS {
+    name = name
+      .strip_suffix(&format!(".{}", extension))
+      .unwrap_or(name);
+  }
+
+  let (stem, extension) = name.rsplit_once('.')?;
+  if stem.is_empty() {
+    return None;
+  }
+
+  Some(extension.to_string())
+}
+
+fn parse_type(name: &str) -> Result<TypeSpecifierNonArray, Error> {
+  TypeSpecifierNonArray::parse(name).map_err(|_| {
+    Error::new(
+      ErrorKind::InvalidOperation,
+      format!("{} is not a GLSL type", name),
+    )
+  })
+}
+
+/// Adds the filters that map GLSL types and names to other languages, e.g.
+/// `{{ uniform.type | ts_type }}` or `{{ uniform.name | snake_case }}`.
+fn add_filters(environment: &mut Environment) {
+  environment.add_filter("ts_type", |name: &str| -> Result<String, Error> {
+    Ok(value_type(&parse_type(name)?))
+  });
+
+  environment.add_filter("rust_type", |name: &str| -> Result<String, Error> {
+    match layout::field_type(&parse_type(name)?) {
+      Some(ty) => Ok(value_label(&ty)),
+      None => Err(Error::new(
+        ErrorKind::InvalidOperation,
+        format!("{} does not have a Rust type", name),
+      )),
+    }
+  });
+
+  // The number of components of a type, e.g. 9 for a `mat3`, or 1 for an opaque type
+  environment.add_filter("components", |name: &str| -> Result<usize, Error> {
+    match layout::field_type(&parse_type(name)?) {
+      Some(FieldType::Value { columns, rows, .. }) => Ok(columns * rows),
+      _ => Ok(1),
+    }
+  });
+
+  environment.add_filter("snake_case", |name: &str| to_snake_case(name));
+  environment.add_filter("pascal_case", |name: &str| {
+    naming::to_case(name, NamingConvention::Pascal)
+  });
+  environment.add_filter("camel_case", |name: &str| {
+    naming::to_case(name, NamingConvention::Camel)
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::add_filters;
+  use minijinja::Environment;
+
+  fn render(template: &str) -> String {
+    let mut environment = Environment::new();
+    add_filters(&mut environment);
+    environment.render_str(template, ()).unwrap()
+  }
+
+  #[test]
+  fn maps_glsl_types_to_typescript() {
+    assert_eq!(render("{{ 'float' | ts_type }}"), "number");
+    assert_eq!(render("{{ 'bool' | ts_type }}"), "boolean");
+    assert_eq!(render("{{ 'vec3' | ts_type }}"), "[number, number, number]");
+    assert_eq!(render("{{ 'bvec2' | ts_type }}"), "[boolean, boolean]");
+    assert_eq!(
+      render("{{ 'mat2' | ts_type }}"),
+      "[number, number, number, number]"
+    );
+    assert_eq!(render("{{ 'sampler2D' | ts_type }}"), "number");
+  }
+
+  #[test]
+  fn maps_glsl_types_to_rust() {
+    assert_eq!(render("{{ 'uint' | rust_type }}"), "u32");
+    assert_eq!(render("{{ 'vec3' | rust_type }}"), "[f32; 3]");
+    assert_eq!(render("{{ 'mat2x3' | rust_type }}"), "[[f32; 3]; 2]");
+  }
+
+  #[test]
+  fn changes_the_case_of_names() {
+    assert_eq!(render("{{ 'uLightColor' | snake_case }}"), "u_light_color");
+    assert_eq!(render("{{ 'light-color' | camel_case }}"), "lightColor");
+  }
+}
@@ -1,133 +1,1018 @@
-use super::common;
-use crate::{
-  log, log_with_color, logln,
-  utils::{
-    get_shader_type::{get_shader_type, ShaderType},
-    log::{print_level, Level},
-  },
-  write_file,
-};
+use super::common::{self, ShaderVariant};
+use super::layout::{self, FieldType, ScalarKind};
+use super::naming;
+use super::vertex_layout::{self, VertexLayout};
+use super::{Generator, OutputFile, Reflection};
+use crate::config::{DefineValue, Emit, ModuleFormat, Settings, SourceMode, Target};
+use crate::utils::get_shader_type::ShaderType;
 use glsl::syntax::TypeSpecifierNonArray;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-pub fn generate_types_file(
-  content: String,
-  file_path: &std::path::PathBuf,
-  output_folder: &std::path::PathBuf,
-) -> bool {
-  let shader_type = get_shader_type(file_path).unwrap();
-  let shader_data = common::extract_shader_data(&content, shader_type);
+/// How the module is written: as TypeScript, or as JavaScript with its types in a `.d.ts` file.
+#[derive(Clone, Copy)]
+struct ModuleStyle {
+  typed: bool,
+  commonjs: bool,
+  as_const: bool,
+  source_mode: SourceMode,
+  /// WebGL 1 has no vertex array objects in its core API, nor integer attributes.
+  webgl1: bool,
+}
+
+impl ModuleStyle {
+  fn export(&self) -> &'static str {
+    if self.commonjs {
+      ""
+    } else {
+      "export "
+    }
+  }
+
+  fn const_suffix(&self) -> &'static str {
+    if self.as_const {
+      " as const"
+    } else {
+      ""
+    }
+  }
+}
 
-  // Show a warning if the vertex shader has more than 16 attributes (This is webgl2)
-  if shader_data.ins.len() > 16 && shader_data.shader_type == ShaderType::Vertex {
-    print_level(Level::WARN);
-    log("The vertex shader ");
-    log_with_color(file_path.to_str().unwrap(), "blue");
-    logln(" has more than 16 attributes. This can cause issues in some devices.");
+pub struct TypeScriptGenerator;
+
+impl Generator for TypeScriptGenerator {
+  fn name(&self) -> &str {
+    "typescript"
   }
 
+  /// Generates the types of a shader, and a typed `getVariant` accessor for its variants. The
+  /// output is a `.ts` module, or a `.js` module (ES or CommonJS) with a `.d.ts` file.
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let output_type_name = &reflection.export_name;
+    if let Some(constant) = reflection
+      .data
+      .constants
+      .iter()
+      .find(|constant| &constant.identifier == output_type_name)
+    {
+      return Err(format!(
+        "the export {} has the name of a constant, set `export_prefix` or `export_suffix`",
+        constant.identifier
+      ));
+    }
+
+    // Constants keep their names in the module, so that the host code uses the names of the shader
+    if let Some(constant) = reflection
+      .data
+      .constants
+      .iter()
+      .find(|constant| naming::is_reserved(&constant.identifier))
+    {
+      return Err(format!(
+        "the constant {} has the name of a reserved word or of a declaration of the module, rename it",
+        constant.identifier
+      ));
+    }
+
+    let typed = settings.emit() == Emit::Ts;
+    if !typed && settings.module() == ModuleFormat::Cjs && settings.source_mode() == SourceMode::Raw
+    {
+      return Err("the raw source mode imports the source, which needs ES modules".to_string());
+    }
+
+    // JavaScript has no `as const`, the `.d.ts` file has the literal types instead
+    let style = ModuleStyle {
+      typed,
+      commonjs: !typed && settings.module() == ModuleFormat::Cjs,
+      as_const: typed && settings.as_const(),
+      source_mode: settings.source_mode(),
+      webgl1: settings.target() == Target::WebGL1,
+    };
+
+    let vertex_layout = if settings.vertex_layout() && reflection.stage == ShaderType::Vertex {
+      Some(vertex_layout::vertex_layout(&reflection.data.ins)?)
+    } else {
+      None
+    };
+    let module = format_module(reflection, output_type_name, vertex_layout.as_ref(), style);
+
+    let mut files = match settings.emit() {
+      Emit::Ts => vec![OutputFile {
+        path: PathBuf::from(format!("{}.ts", reflection.name)),
+        contents: module,
+      }],
+      Emit::Js => vec![
+        OutputFile {
+          path: PathBuf::from(format!("{}.js", reflection.name)),
+          contents: module,
+        },
+        OutputFile {
+          path: PathBuf::from(format!("{}.d.ts", reflection.name)),
+          contents: format_declarations(
+            reflection,
+            output_type_name,
+            vertex_layout.as_ref(),
+            settings.as_const(),
+            style.webgl1,
+          ),
+        },
+      ],
+    };
+
+    if style.source_mode == SourceMode::Raw {
+      files.push(OutputFile {
+        path: PathBuf::from(source_file(&reflection.name, None)),
+        contents: reflection.shader.source.clone(),
+      });
+
+      for (i, variant) in reflection.variants.iter().enumerate() {
+        files.push(OutputFile {
+          path: PathBuf::from(source_file(&reflection.name, Some(i))),
+          contents: variant.shader.source.clone(),
+        });
+      }
+    }
+
+    Ok(files)
+  }
+}
+
+fn format_module(
+  reflection: &Reflection,
+  output_type_name: &str,
+  vertex_layout: Option<&VertexLayout>,
+  style: ModuleStyle,
+) -> String {
+  let shader = &reflection.shader;
+  let shader_data = &reflection.data;
+  let mut exports = Vec::new();
+
   let mut output_file = String::new();
   output_file.push_str("// DO NOT EDIT THIS FILE\n");
   output_file.push_str("// This file is generated by glsl-types\n\n");
 
-  output_file.push_str(&format!("const SOURCE = /* glsl */ `{}`;\n\n", &content));
+  if style.commonjs {
+    output_file.push_str("\"use strict\";\n\n");
+  }
+
+  output_file.push_str(&format_source(
+    "SOURCE",
+    &shader.source,
+    &source_file(&reflection.name, None),
+    style,
+  ));
+
+  // Constants are exported on their own so that the host code can use them like the shader does
+  if !shader_data.constants.is_empty() {
+    let constants = shader_data
+      .constants
+      .iter()
+      .map(|constant| {
+        exports.push(constant.identifier.clone());
+        format!(
+          "{}const {} = {}; // {}",
+          style.export(),
+          constant.identifier,
+          constant.value,
+          common::glsl_type_name(&constant.type_label)
+        )
+      })
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    output_file.push_str(&format!("{}\n", constants));
+  }
+
+  // Export a type that contains all the uniforms
+  exports.push(output_type_name.to_string());
+  output_file.push_str(&format!(
+    r#"
 
-  let output_file_name = file_path.file_stem().unwrap().to_str().unwrap();
-  let output_type_name = common::capitalize_first_letter(output_file_name);
+{}const {} = {{
+{}{}}}{};
 
-  let uniforms = shader_data
-    .uniforms
+"#,
+    style.export(),
+    output_type_name,
+    format_defines_field(&shader.defines),
+    format_shader_fields(shader_data, reflection.stage, &shader.names, "SOURCE"),
+    style.const_suffix()
+  ));
+
+  if let Some(vertex_layout) = vertex_layout {
+    exports.extend(VERTEX_LAYOUT_EXPORTS.map(String::from));
+    output_file.push_str(&format_vertex_layout(vertex_layout, &shader.names, style));
+  }
+
+  if !reflection.variants.is_empty() {
+    exports.push("getVariant".to_string());
+    output_file.push_str(&format_variants(
+      &reflection.name,
+      reflection.stage,
+      &reflection.variants,
+      style,
+    ));
+  }
+
+  if style.commonjs {
+    output_file.push_str(&format!(
+      "\nmodule.exports = {{ {} }};\n",
+      exports.join(", ")
+    ));
+  }
+
+  output_file
+}
+
+/// Gets the name of the `.glsl` file of a shader, or of one of its variants, in the raw mode.
+fn source_file(name: &str, variant: Option<usize>) -> String {
+  match variant {
+    Some(i) => format!("{}_variant_{}.glsl", name, i),
+    None => format!("{}.glsl", name),
+  }
+}
+
+/// Escapes a source for a template literal, in which backticks end the literal, `${` starts an
+/// expression and backslashes start escape sequences.
+fn escape_template_literal(source: &str) -> String {
+  source
+    .replace('\\', "\\\\")
+    .replace('`', "\\`")
+    .replace("${", "\\${")
+}
+
+/// Formats the declaration of `constant`, which has a shader source. In the raw mode, the source
+/// is imported from `file` (written next to the module) with the `?raw` query of bundlers.
+fn format_source(constant: &str, source: &str, file: &str, style: ModuleStyle) -> String {
+  match style.source_mode {
+    SourceMode::Template => format!(
+      "const {} = /* glsl */ `{}`;\n\n",
+      constant,
+      escape_template_literal(source)
+    ),
+    SourceMode::Json => format!(
+      "const {} = {};\n\n",
+      constant,
+      serde_json::to_string(source).unwrap()
+    ),
+    SourceMode::Raw => format!("import {} from \"./{}?raw\";\n\n", constant, file),
+  }
+}
+
+fn format_variables(variables: &[common::TypedVariable]) -> String {
+  variables
     .iter()
     .map(|value| {
       format!(
         r#"    {}: "{}","#,
         &value.identifier,
-        convert_glsl_to_ts_label(&value.type_label)
+        common::glsl_type_name(&value.type_label)
       )
     })
     .collect::<Vec<String>>()
+    .join("\n")
+}
+
+/// Formats the fields of the object exported for a shader, up to its `source`.
+fn format_shader_fields(
+  shader_data: &common::ShaderData,
+  stage: ShaderType,
+  names: &BTreeMap<String, String>,
+  source: &str,
+) -> String {
+  // Only shaders with mangled names need the map to the names used in the source
+  let names = if names.is_empty() {
+    String::new()
+  } else {
+    let names = names
+      .iter()
+      .map(|(name, mangled)| format!(r#"    {}: "{}","#, name, mangled))
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    format!("  names: {{\n{}\n  }},\n", names)
+  };
+
+  let kinds = if stage == ShaderType::Vertex {
+    let kinds = attribute_kinds(&shader_data.ins)
+      .map(|(name, kind)| format!(r#"    {}: "{}","#, name, kind))
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    format!("  kinds: {{\n{}\n  }},\n", kinds)
+  } else {
+    String::new()
+  };
+
+  format!(
+    r#"  uniforms: {{
+{}
+  }},
+  ins: {{
+{}
+  }},
+{}  outs: {{
+{}
+  }},
+{}  source: {}
+"#,
+    format_variables(&shader_data.uniforms),
+    format_variables(&shader_data.ins),
+    kinds,
+    format_variables(&shader_data.outs),
+    names,
+    source
+  )
+}
+
+fn format_define_value(value: &DefineValue) -> String {
+  match value {
+    DefineValue::Bool(value) => value.to_string(),
+    DefineValue::Integer(value) => value.to_string(),
+    DefineValue::Float(value) => format!("{:?}", value),
+    DefineValue::Text(value) => serde_json::to_string(value).unwrap(),
+  }
+}
+
+/// Formats the `defines` field of a shader object, which records the defines the shader was
+/// compiled with. Shaders without defines do not have it.
+fn format_defines_field(defines: &[(String, DefineValue)]) -> String {
+  if defines.is_empty() {
+    return String::new();
+  }
+
+  let defines = defines
+    .iter()
+    .map(|(name, value)| format!("    {}: {},", name, format_define_value(value)))
+    .collect::<Vec<String>>()
     .join("\n");
 
-  let ins = shader_data
-    .ins
+  format!("  defines: {{\n{}\n  }},\n", defines)
+}
+
+/// Formats a variant as the type of the argument of `getVariant`. Every define is optional, as
+/// a missing define takes the value it has in the first variant.
+fn format_defines_type(defines: &[(String, String)]) -> String {
+  let fields = defines
     .iter()
-    .map(|value| {
+    .map(|(name, value)| format!("{}?: {}", name, value))
+    .collect::<Vec<String>>()
+    .join("; ");
+
+  format!("{{ {} }}", fields)
+}
+
+/// Formats the variants of a shader and `getVariant`, which finds the variant for some defines.
+fn format_variants(
+  name: &str,
+  stage: ShaderType,
+  variants: &[ShaderVariant],
+  style: ModuleStyle,
+) -> String {
+  let mut output = String::new();
+
+  for (i, variant) in variants.iter().enumerate() {
+    output.push_str(&format_source(
+      &format!("VARIANT_{}_SOURCE", i),
+      &variant.shader.source,
+      &source_file(name, Some(i)),
+      style,
+    ));
+
+    output.push_str(&format!(
+      "const VARIANT_{} = {{\n{}{}}}{};\n\n",
+      i,
+      format_defines_field(&variant.defines),
+      format_shader_fields(
+        &variant.data,
+        stage,
+        &variant.shader.names,
+        &format!("VARIANT_{}_SOURCE", i)
+      ),
+      style.const_suffix()
+    ));
+  }
+
+  let variant_names = (0..variants.len())
+    .map(|i| format!("VARIANT_{}", i))
+    .collect::<Vec<String>>()
+    .join(", ");
+
+  output.push_str(&format!(
+    "const VARIANTS = [{}]{};\n\n",
+    variant_names,
+    style.const_suffix()
+  ));
+
+  if style.typed {
+    output.push_str(&format!(
+      r#"{}
+export function getVariant(defines: Partial<Record<string, boolean | number | string>>) {{
+  const first: Record<string, boolean | number | string> = VARIANTS[0].defines;
+  return VARIANTS.find((variant) => {{
+    const values: Record<string, boolean | number | string> = variant.defines;
+    return Object.keys(values).every((name) => (defines[name] ?? first[name]) === values[name]);
+  }})!;
+}}
+"#,
+      format_variant_overloads(variants, "export function")
+    ));
+  } else {
+    output.push_str(&format!(
+      r#"{}function getVariant(defines) {{
+  const first = VARIANTS[0].defines;
+  return VARIANTS.find((variant) => {{
+    const values = variant.defines;
+    return Object.keys(values).every((name) => (defines[name] ?? first[name]) === values[name]);
+  }});
+}}
+"#,
+      style.export()
+    ));
+  }
+
+  output
+}
+
+/// Formats the overloads of `getVariant`, one per variant, so that its return type only has the
+/// uniforms and attributes of the requested variant.
+fn format_variant_overloads(variants: &[ShaderVariant], keyword: &str) -> String {
+  let mut overloads = Vec::new();
+
+  for (i, variant) in variants.iter().enumerate() {
+    let defines = variant
+      .defines
+      .iter()
+      .map(|(name, value)| (name.clone(), format_define_value(value)))
+      .collect::<Vec<(String, String)>>();
+
+    overloads.push(format!(
+      "{} getVariant(defines: {}): typeof VARIANT_{};",
+      keyword,
+      format_defines_type(&defines),
+      i
+    ));
+  }
+
+  // Accept any combination of the values too, e.g. from a boolean that is not a literal
+  let mut any_defines: Vec<(String, String)> = Vec::new();
+  for variant in variants {
+    for (name, value) in &variant.defines {
+      let value = format_define_value(value);
+      match any_defines.iter_mut().find(|(other, _)| other == name) {
+        Some((_, values)) => {
+          if !values.split(" | ").any(|other| other == value) {
+            values.push_str(&format!(" | {}", value));
+          }
+        }
+        None => any_defines.push((name.clone(), value)),
+      }
+    }
+  }
+
+  overloads.push(format!(
+    "{} getVariant(defines: {}): (typeof VARIANTS)[number];",
+    keyword,
+    format_defines_type(&any_defines)
+  ));
+
+  overloads.join("\n")
+}
+
+/// Formats the `.d.ts` file of a `.js` module. With `as_const` the fields have the literal types
+/// of their values, like the `.ts` output with `as const`, otherwise they are widened.
+fn format_declarations(
+  reflection: &Reflection,
+  output_type_name: &str,
+  vertex_layout: Option<&VertexLayout>,
+  as_const: bool,
+  webgl1: bool,
+) -> String {
+  let shader = &reflection.shader;
+  let mut output_file = String::new();
+  output_file.push_str("// DO NOT EDIT THIS FILE\n");
+  output_file.push_str("// This file is generated by glsl-types\n\n");
+
+  // Constants are `const` in the module, so their literal types are always known
+  for constant in &reflection.data.constants {
+    output_file.push_str(&format!(
+      "export declare const {}: {}; // {}\n",
+      constant.identifier,
+      constant.value,
+      common::glsl_type_name(&constant.type_label)
+    ));
+  }
+
+  if !reflection.data.constants.is_empty() {
+    output_file.push('\n');
+  }
+
+  output_file.push_str(&format!(
+    "export declare const {}: {};\n",
+    output_type_name,
+    format_shader_type(
+      &shader.defines,
+      &reflection.data,
+      reflection.stage,
+      &shader.names,
+      as_const
+    )
+  ));
+
+  if let Some(vertex_layout) = vertex_layout {
+    output_file.push_str(&format_vertex_layout_declarations(vertex_layout, webgl1));
+  }
+
+  if reflection.variants.is_empty() {
+    return output_file;
+  }
+
+  output_file.push('\n');
+  for (i, variant) in reflection.variants.iter().enumerate() {
+    output_file.push_str(&format!(
+      "declare const VARIANT_{}: {};\n\n",
+      i,
+      format_shader_type(
+        &variant.defines,
+        &variant.data,
+        reflection.stage,
+        &variant.shader.names,
+        as_const
+      )
+    ));
+  }
+
+  let variant_types = (0..reflection.variants.len())
+    .map(|i| format!("typeof VARIANT_{}", i))
+    .collect::<Vec<String>>();
+  let variants_type = if as_const {
+    format!("readonly [{}]", variant_types.join(", "))
+  } else {
+    format!("({})[]", variant_types.join(" | "))
+  };
+
+  output_file.push_str(&format!("declare const VARIANTS: {};\n\n", variants_type));
+  output_file.push_str(&format_variant_overloads(
+    &reflection.variants,
+    "export declare function",
+  ));
+
+  // Without it, the declarations of the variants would be exported too
+  output_file.push_str("\n\nexport {};\n");
+
+  output_file
+}
+
+/// Formats the type of the object exported for a shader, as TypeScript infers it from the object.
+fn format_shader_type(
+  defines: &[(String, DefineValue)],
+  shader_data: &common::ShaderData,
+  stage: ShaderType,
+  names: &BTreeMap<String, String>,
+  as_const: bool,
+) -> String {
+  let readonly = if as_const { "readonly " } else { "" };
+  let literal = |value: String, widened: &str| {
+    if as_const {
+      value
+    } else {
+      widened.to_string()
+    }
+  };
+  let object = |fields: Vec<(String, String)>| {
+    let fields = fields
+      .into_iter()
+      .map(|(name, value)| format!("    {}{}: {};\n", readonly, name, value))
+      .collect::<String>();
+
+    format!("{{\n{}  }}", fields)
+  };
+  let variables = |variables: &[common::TypedVariable]| {
+    object(
+      variables
+        .iter()
+        .map(|variable| {
+          let label = format!(r#""{}""#, common::glsl_type_name(&variable.type_label));
+          (variable.identifier.clone(), literal(label, "string"))
+        })
+        .collect(),
+    )
+  };
+
+  let mut fields = Vec::new();
+  if !defines.is_empty() {
+    let defines = defines
+      .iter()
+      .map(|(name, value)| {
+        let widened = match value {
+          DefineValue::Bool(_) => "boolean",
+          DefineValue::Integer(_) | DefineValue::Float(_) => "number",
+          DefineValue::Text(_) => "string",
+        };
+        (name.clone(), literal(format_define_value(value), widened))
+      })
+      .collect();
+    fields.push(("defines", object(defines)));
+  }
+
+  fields.push(("uniforms", variables(&shader_data.uniforms)));
+  fields.push(("ins", variables(&shader_data.ins)));
+
+  if stage == ShaderType::Vertex {
+    let kinds = attribute_kinds(&shader_data.ins)
+      .map(|(name, kind)| {
+        (
+          name.to_string(),
+          literal(format!(r#""{}""#, kind), ATTRIBUTE_KIND_TYPE),
+        )
+      })
+      .collect();
+    fields.push(("kinds", object(kinds)));
+  }
+
+  fields.push(("outs", variables(&shader_data.outs)));
+
+  if !names.is_empty() {
+    let names = names
+      .iter()
+      .map(|(name, mangled)| (name.clone(), literal(format!(r#""{}""#, mangled), "string")))
+      .collect();
+    fields.push(("names", object(names)));
+  }
+
+  fields.push(("source", "string".to_string()));
+
+  let fields = fields
+    .into_iter()
+    .map(|(name, value)| format!("  {}{}: {};\n", readonly, name, value))
+    .collect::<String>();
+
+  format!("{{\n{}}}", fields)
+}
+
+/// The type of the kind of an attribute, which can not be a `bool`.
+const ATTRIBUTE_KIND_TYPE: &str = r#""float" | "int" | "uint""#;
+
+/// Gets the type of the components of the attributes of a vertex shader, which the host code
+/// needs to call `vertexAttribIPointer` rather than `vertexAttribPointer` for the integer ones.
+fn attribute_kinds(
+  ins: &[common::TypedVariable],
+) -> impl Iterator<Item = (&str, &'static str)> + '_ {
+  ins.iter().filter_map(|input| {
+    let kind = input.scalar_kind()?;
+    Some((input.identifier.as_str(), kind.name()))
+  })
+}
+
+/// The names declared for the vertex layout of a vertex shader.
+const VERTEX_LAYOUT_EXPORTS: [&str; 4] = [
+  "VERTEX_LAYOUT",
+  "bindAttributeLocations",
+  "setupAttributes",
+  "packVertices",
+];
+
+/// Formats the type of an attribute in a vertex: a number, or a tuple with a number per
+/// component.
+fn format_component_type(components: usize) -> String {
+  if components == 1 {
+    return "number".to_string();
+  }
+
+  format!("[{}]", vec!["number"; components].join(", "))
+}
+
+/// Formats the type of a value of a GLSL type: a number or a boolean, a tuple with a component
+/// per element for vectors and matrices, or the name of a struct. Samplers are set to their
+/// texture unit, a number.
+pub fn value_type(ty: &TypeSpecifierNonArray) -> String {
+  match layout::field_type(ty) {
+    Some(FieldType::Value {
+      kind,
+      columns,
+      rows,
+    }) => {
+      let component = match kind {
+        ScalarKind::Bool => "boolean",
+        _ => "number",
+      };
+
+      match columns * rows {
+        1 => component.to_string(),
+        count => format!("[{}]", vec![component; count].join(", ")),
+      }
+    }
+    Some(FieldType::Struct(name)) => name,
+    None => "number".to_string(),
+  }
+}
+
+fn format_vertex_type(vertex_layout: &VertexLayout) -> String {
+  let fields = vertex_layout
+    .attributes
+    .iter()
+    .map(|attribute| {
       format!(
-        r#"    {}: "{}","#,
-        &value.identifier,
-        convert_glsl_to_ts_label(&value.type_label)
+        "  {}: {};\n",
+        attribute.name,
+        format_component_type(attribute.components)
       )
     })
-    .collect::<Vec<String>>()
-    .join("\n");
+    .collect::<String>();
+
+  format!("{{\n{}}}", fields)
+}
+
+/// Formats the signatures of the vertex layout functions, with the types of their parameters
+/// when `typed`.
+fn format_vertex_layout_signatures(typed: bool, webgl1: bool) -> [String; 3] {
+  let context = if webgl1 {
+    "WebGLRenderingContext"
+  } else {
+    "WebGL2RenderingContext"
+  };
+  let annotate = |name: &str, ty: &str| {
+    if typed {
+      format!("{}: {}", name, ty)
+    } else {
+      name.to_string()
+    }
+  };
+  let returns = |ty: &str| {
+    if typed {
+      format!(": {}", ty)
+    } else {
+      String::new()
+    }
+  };
+
+  let setup_parameters = if webgl1 {
+    annotate("gl", context)
+  } else {
+    format!(
+      "{}, {}",
+      annotate("gl", context),
+      annotate("vao", "WebGLVertexArrayObject")
+    )
+  };
 
-  let outs = shader_data
-    .outs
+  [
+    format!(
+      "bindAttributeLocations({}, {}){}",
+      annotate("gl", context),
+      annotate("program", "WebGLProgram"),
+      returns("void")
+    ),
+    format!("setupAttributes({}){}", setup_parameters, returns("void")),
+    format!(
+      "packVertices({}){}",
+      annotate("vertices", "Vertex[]"),
+      returns("ArrayBuffer")
+    ),
+  ]
+}
+
+/// Formats the vertex layout of a vertex shader with its attributes interleaved in a buffer:
+/// `VERTEX_LAYOUT` with the stride and the offsets, `bindAttributeLocations` for the inputs
+/// without a location, `setupAttributes` to point the attributes at the bound buffer, and
+/// `packVertices` to fill a buffer with vertices.
+fn format_vertex_layout(
+  vertex_layout: &VertexLayout,
+  names: &BTreeMap<String, String>,
+  style: ModuleStyle,
+) -> String {
+  let stride = vertex_layout.stride;
+  let mut output = String::new();
+
+  let attributes = vertex_layout
+    .attributes
     .iter()
-    .map(|value| {
+    .map(|attribute| {
       format!(
-        r#"    {}: "{}","#,
-        &value.identifier,
-        convert_glsl_to_ts_label(&value.type_label)
+        "    {}: {{ location: {}, kind: \"{}\", components: {}, offset: {} }},\n",
+        attribute.name,
+        attribute.slots[0].location,
+        attribute.kind.name(),
+        attribute.components,
+        attribute.offset
       )
     })
-    .collect::<Vec<String>>()
-    .join("\n");
+    .collect::<String>();
 
-  // Export a type that contains all the uniforms
-  output_file.push_str(&format!(
-    r#"
+  output.push_str(&format!(
+    "{}const VERTEX_LAYOUT = {{\n  stride: {},\n  attributes: {{\n{}  }},\n}}{};\n\n",
+    style.export(),
+    stride,
+    attributes,
+    style.const_suffix()
+  ));
 
-export const {} = {{
-  uniforms: {{
-{}
-  }},
-  ins: {{
-{}
-  }},
-  outs: {{
-{}
-  }},
-  source: SOURCE
-}};
+  if style.typed {
+    output.push_str(&format!(
+      "export type Vertex = {};\n\n",
+      format_vertex_type(vertex_layout)
+    ));
+  }
+
+  let [bind, setup, pack] = format_vertex_layout_signatures(style.typed, style.webgl1);
+
+  // The name in the source can be mangled
+  let bindings = vertex_layout
+    .attributes
+    .iter()
+    .map(|attribute| {
+      let name = names.get(&attribute.name).unwrap_or(&attribute.name);
+      format!(
+        "  gl.bindAttribLocation(program, {}, \"{}\");\n",
+        attribute.slots[0].location, name
+      )
+    })
+    .collect::<String>();
+
+  output.push_str(&format!(
+    "{}function {} {{\n{}}}\n\n",
+    style.export(),
+    bind,
+    bindings
+  ));
+
+  let mut pointers = String::new();
+  if !style.webgl1 {
+    pointers.push_str("  gl.bindVertexArray(vao);\n");
+  }
+
+  for attribute in &vertex_layout.attributes {
+    for slot in &attribute.slots {
+      pointers.push_str(&format!(
+        "  gl.enableVertexAttribArray({});\n",
+        slot.location
+      ));
+      pointers.push_str(&match attribute.kind {
+        ScalarKind::Int | ScalarKind::UInt => format!(
+          "  gl.vertexAttribIPointer({}, {}, gl.{}, {}, {});\n",
+          slot.location,
+          slot.size,
+          if attribute.kind == ScalarKind::Int {
+            "INT"
+          } else {
+            "UNSIGNED_INT"
+          },
+          stride,
+          slot.offset
+        ),
+        _ => format!(
+          "  gl.vertexAttribPointer({}, {}, gl.FLOAT, false, {}, {});\n",
+          slot.location, slot.size, stride, slot.offset
+        ),
+      });
+    }
+  }
+
+  output.push_str(&format!(
+    "{}function {} {{\n{}}}\n\n",
+    style.export(),
+    setup,
+    pointers
+  ));
+
+  let setters = vertex_layout
+    .attributes
+    .iter()
+    .map(|attribute| {
+      let setter = match attribute.kind {
+        ScalarKind::Int => "setInt32",
+        ScalarKind::UInt => "setUint32",
+        _ => "setFloat32",
+      };
+
+      if attribute.components == 1 {
+        format!(
+          "    view.{}(offset + {}, vertex.{}, true);\n",
+          setter, attribute.offset, attribute.name
+        )
+      } else {
+        format!(
+          "    for (let j = 0; j < {}; j++) view.{}(offset + {} + j * 4, vertex.{}[j], true);\n",
+          attribute.components, setter, attribute.offset, attribute.name
+        )
+      }
+    })
+    .collect::<String>();
+
+  output.push_str(&format!(
+    r#"{}function {} {{
+  const buffer = new ArrayBuffer(vertices.length * {});
+  const view = new DataView(buffer);
+  vertices.forEach((vertex, i) => {{
+    const offset = i * {};
+{}  }});
+  return buffer;
+}}
 
 "#,
-    output_type_name, uniforms, ins, outs
+    style.export(),
+    pack,
+    stride,
+    stride,
+    setters
   ));
 
-  let output_file_path = output_folder.join(format!("{}.ts", output_file_name));
-  // std::fs::write(output_file_path, output_file).unwrap();
-  write_file(output_file_path.to_str().unwrap(), output_file.as_str());
+  output
+}
+
+fn format_vertex_layout_declarations(vertex_layout: &VertexLayout, webgl1: bool) -> String {
+  let attributes = vertex_layout
+    .attributes
+    .iter()
+    .map(|attribute| {
+      format!(
+        "    readonly {}: {{ readonly location: number; readonly kind: {}; readonly components: number; readonly offset: number; }};\n",
+        attribute.name,
+        ATTRIBUTE_KIND_TYPE
+      )
+    })
+    .collect::<String>();
+
+  let [bind, setup, pack] = format_vertex_layout_signatures(true, webgl1);
+
+  format!(
+    r#"
+export declare const VERTEX_LAYOUT: {{
+  readonly stride: number;
+  readonly attributes: {{
+{}  }};
+}};
+
+export type Vertex = {};
 
-  return true;
+export declare function {};
+export declare function {};
+export declare function {};
+"#,
+    attributes,
+    format_vertex_type(vertex_layout),
+    bind,
+    setup,
+    pack
+  )
 }
 
-fn convert_glsl_to_ts_label(uniform: &TypeSpecifierNonArray) -> String {
-  let result = match uniform {
-    TypeSpecifierNonArray::Float => "float",
-    TypeSpecifierNonArray::Vec2 => "vec2",
-    TypeSpecifierNonArray::Vec3 => "vec3",
-    TypeSpecifierNonArray::Vec4 => "vec4",
+#[cfg(test)]
+mod tests {
+  use super::TypeScriptGenerator;
+  use crate::config::Settings;
+  use crate::generator::{test_reflection, Generator};
+  use crate::utils::get_shader_type::ShaderType;
 
-    TypeSpecifierNonArray::Int => "int",
-    TypeSpecifierNonArray::IVec2 => "ivec2",
-    TypeSpecifierNonArray::IVec3 => "ivec3",
-    TypeSpecifierNonArray::IVec4 => "ivec4",
+  fn generate(source: &str) -> Result<Vec<String>, String> {
+    let reflection = test_reflection("lit_frag", source, ShaderType::Fragment);
+    let files = TypeScriptGenerator.generate(&reflection, &Settings::default())?;
 
-    TypeSpecifierNonArray::UInt => "uint",
-    TypeSpecifierNonArray::UVec2 => "uvec2",
-    TypeSpecifierNonArray::UVec3 => "uvec3",
-    TypeSpecifierNonArray::UVec4 => "uvec4",
+    Ok(files.into_iter().map(|file| file.contents).collect())
+  }
 
-    TypeSpecifierNonArray::Bool => "bool",
-    TypeSpecifierNonArray::BVec2 => "bvec2",
-    TypeSpecifierNonArray::BVec3 => "bvec3",
-    TypeSpecifierNonArray::BVec4 => "bvec4",
+  #[test]
+  fn exports_the_constants() {
+    let files = generate(
+      "#version 300 es
+precision highp float;
+#define MAX_LIGHTS 8
+const float PI = 3.14159;
+void main() {}
+",
+    )
+    .unwrap();
 
-    TypeSpecifierNonArray::Mat2 => "mat2",
-    TypeSpecifierNonArray::Mat3 => "mat3",
-    TypeSpecifierNonArray::Mat4 => "mat4",
-    _ => "UNKNOWN",
-  };
+    assert!(
+      files[0].contains("export const MAX_LIGHTS = 8; // int"),
+      "{}",
+      files[0]
+    );
+    assert!(
+      files[0].contains("export const PI = 3.14159; // float"),
+      "{}",
+      files[0]
+    );
+  }
+
+  #[test]
+  fn rejects_constants_named_like_a_declaration_of_the_module() {
+    for name in ["SOURCE", "getVariant", "VARIANT_0", "VARIANT_0_SOURCE"] {
+      let source = format!(
+        "#version 300 es
+precision highp float;
+#define {} 1
+void main() {{}}
+",
+        name
+      );
 
-  return result.to_string();
+      let error = generate(&source).unwrap_err();
+      assert!(error.contains(name), "{}", error);
+    }
+  }
 }
@@ -0,0 +1,124 @@
+use super::common::TypedVariable;
+use super::layout::{self, FieldType, ScalarKind};
+
+/// The bytes taken by a component of an attribute in a vertex buffer.
+const COMPONENT_SIZE: usize = 4;
+
+/// A location of an attribute in an interleaved vertex buffer. Matrices and arrays take a
+/// location per column and per element.
+#[derive(Clone, Debug)]
+pub struct AttributeSlot {
+  pub location: usize,
+  /// The number of components read at the location, from 1 to 4.
+  pub size: usize,
+  pub offset: usize,
+}
+
+/// An input of a vertex shader in an interleaved vertex buffer.
+#[derive(Clone, Debug)]
+pub struct VertexAttribute {
+  pub name: String,
+  pub kind: ScalarKind,
+  /// The number of components of the attribute in a vertex, e.g. 16 for a `mat4`.
+  pub components: usize,
+  pub offset: usize,
+  pub slots: Vec<AttributeSlot>,
+}
+
+/// The attributes of a vertex shader packed one after the other in a single buffer.
+#[derive(Clone, Debug)]
+pub struct VertexLayout {
+  pub stride: usize,
+  pub attributes: Vec<VertexAttribute>,
+}
+
+/// The number of locations an input takes, e.g. 4 for a `mat4`.
+fn location_count(input: &TypedVariable) -> usize {
+  let columns = match layout::field_type(&input.type_label) {
+    Some(FieldType::Value { columns, .. }) => columns,
+    _ => 1,
+  };
+  columns * input.array_size.unwrap_or(1)
+}
+
+/// Gets the locations of the inputs of a vertex shader. Inputs without a `layout(location = N)`
+/// get the next free locations, to bind them with `bindAttribLocation` before linking.
+pub fn attribute_locations(ins: &[TypedVariable]) -> Vec<usize> {
+  let mut taken = ins
+    .iter()
+    .filter_map(|input| Some((input.location?, location_count(input))))
+    .flat_map(|(location, count)| location..location + count)
+    .collect::<Vec<usize>>();
+
+  ins
+    .iter()
+    .map(|input| {
+      input.location.unwrap_or_else(|| {
+        let count = location_count(input);
+        let location = (0..)
+          .find(|location| (*location..*location + count).all(|slot| !taken.contains(&slot)))
+          .unwrap();
+        taken.extend(location..location + count);
+        location
+      })
+    })
+    .collect()
+}
+
+/// Lays out the inputs of a vertex shader in an interleaved buffer, in the order they are
+/// declared in. Every component takes 4 bytes.
+pub fn vertex_layout(ins: &[TypedVariable]) -> Result<VertexLayout, String> {
+  let mut attributes = Vec::new();
+  let mut offset = 0;
+
+  for (input, location) in ins.iter().zip(attribute_locations(ins)) {
+    let (kind, columns, rows) = match layout::field_type(&input.type_label) {
+      Some(FieldType::Value {
+        kind: ScalarKind::Bool,
+        ..
+      })
+      | Some(FieldType::Struct(_))
+      | None => {
+        return Err(format!(
+          "the input {} can not be in a vertex buffer",
+          input.identifier
+        ))
+      }
+      Some(FieldType::Value {
+        kind,
+        columns,
+        rows,
+      }) => (kind, columns, rows),
+    };
+
+    if input.is_array && input.array_size.is_none() {
+      return Err(format!(
+        "the size of the input {} is not known",
+        input.identifier
+      ));
+    }
+
+    let slots = (0..columns * input.array_size.unwrap_or(1))
+      .map(|slot| AttributeSlot {
+        location: location + slot,
+        size: rows,
+        offset: offset + slot * rows * COMPONENT_SIZE,
+      })
+      .collect::<Vec<AttributeSlot>>();
+    let components = slots.len() * rows;
+
+    attributes.push(VertexAttribute {
+      name: input.identifier.clone(),
+      kind,
+      components,
+      offset,
+      slots,
+    });
+    offset += components * COMPONENT_SIZE;
+  }
+
+  Ok(VertexLayout {
+    stride: offset,
+    attributes,
+  })
+}
@@ -1,10 +1,15 @@
-use crate::canonicalize;
 use crate::file_exists;
+use crate::preprocessor::preprocess::blank_nested_conditionals;
 use crate::read_file;
+use crate::utils::strip_comments::strip_comments;
 
+use super::guards::{detect_include_guard, IncludeGuard};
 use super::import_resolver::ImportError;
+use super::selective_import::{extract_selective_includes, Selection};
 use glsl::parser::Parse as _;
-use glsl::syntax::{FunctionDefinition, PreprocessorInclude, ShaderStage, StructSpecifier};
+use glsl::syntax::{
+  FunctionDefinition, PreprocessorInclude, ShaderStage, StructSpecifier, TranslationUnit,
+};
 use glsl::visitor::{Host, Visit, Visitor};
 use std::path::PathBuf;
 
@@ -15,54 +20,72 @@ pub fn get_file_data(file_path: &PathBuf) -> Result<ImportedFile, ImportError> {
     return Err(ImportError::FileNotFound(file_path.to_path_buf()));
   }
 
-  let contents = read_file(file_path_string);
+  // Line endings depend on how the file was checked out, the outputs should not
+  let contents = read_file(file_path_string).replace("\r\n", "\n");
 
-  let ast = match ShaderStage::parse(&contents) {
+  let (source, selections) = extract_selective_includes(&strip_comments(&contents));
+
+  // Conditionals inside of functions are only understood once the file is preprocessed
+  let ast = match ShaderStage::parse(&source)
+    .or_else(|_| ShaderStage::parse(blank_nested_conditionals(&source)))
+  {
     Ok(ast) => ast,
     Err(_) => return Err(ImportError::CouldNotParseFile(file_path.to_path_buf())),
   };
 
-  let mut visitor = ImportedFileVisitor::new(file_path);
+  let mut visitor = ImportedFileVisitor::new();
   ast.visit(&mut visitor);
 
-  if let Some(error) = visitor.error {
-    return Err(error);
-  }
-
-  return Ok(ImportedFile {
+  let includes = visitor
+    .includes
+    .into_iter()
+    .enumerate()
+    .map(|(i, path)| Include {
+      path,
+      selection: selections.get(i).cloned().unwrap_or(Selection::All),
+    })
+    .collect();
+
+  Ok(ImportedFile {
     structs: visitor.structs,
     functions: visitor.functions,
-    imports: visitor.imports,
+    includes,
+    guard: detect_include_guard(&contents),
     contents,
-  });
+    ast,
+  })
+}
+
+#[derive(Clone, Debug)]
+pub struct Include {
+  pub path: glsl::syntax::Path,
+  /// The symbols requested with `#include "file" : a, b` or `#import { a, b } from "file"`.
+  pub selection: Selection,
 }
 
 #[derive(Clone, Debug)]
 pub struct ImportedFile {
   pub structs: Vec<String>,
   pub functions: Vec<String>,
-  pub imports: Vec<PathBuf>,
+  /// The `#include` directives of the file, in order. They are resolved by the `ImportResolver`.
+  pub includes: Vec<Include>,
+  pub guard: IncludeGuard,
   pub contents: String,
+  pub ast: TranslationUnit,
 }
 
 struct ImportedFileVisitor {
-  path: PathBuf,
-  parent_path: PathBuf,
   structs: Vec<String>,
   functions: Vec<String>,
-  imports: Vec<PathBuf>,
-  error: Option<ImportError>,
+  includes: Vec<glsl::syntax::Path>,
 }
 
 impl ImportedFileVisitor {
-  fn new(path: &PathBuf) -> Self {
+  fn new() -> Self {
     Self {
-      path: path.clone(),
-      parent_path: path.parent().unwrap().to_path_buf(),
       structs: Vec::new(),
       functions: Vec::new(),
-      imports: Vec::new(),
-      error: None,
+      includes: Vec::new(),
     }
   }
 }
@@ -74,30 +97,15 @@ impl Visitor for ImportedFileVisitor {
   }
 
   fn visit_struct_specifier(&mut self, struct_specifier: &StructSpecifier) -> Visit {
-    return match &struct_specifier.name {
-      Some(name) => {
-        self.structs.push(name.to_string());
-        Visit::Parent
-      }
-      None => Visit::Parent,
-    };
-  }
-
-  fn visit_preprocessor_include(&mut self, import: &PreprocessorInclude) -> Visit {
-    let path = match &import.path {
-      glsl::syntax::Path::Absolute(path) => PathBuf::from(path),
-      glsl::syntax::Path::Relative(path) => {
-        PathBuf::from(canonicalize(self.parent_path.join(path).to_str().unwrap()))
-      }
-    };
-
-    if self.imports.contains(&path) {
-      self.error = Some(ImportError::DuplicateImport(self.path.clone(), path));
-      return Visit::Parent;
+    if let Some(name) = &struct_specifier.name {
+      self.structs.push(name.to_string());
     }
 
-    self.imports.push(path);
+    Visit::Parent
+  }
 
+  fn visit_preprocessor_include(&mut self, import: &PreprocessorInclude) -> Visit {
+    self.includes.push(import.path.clone());
     Visit::Parent
   }
 }
@@ -1,14 +1,17 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+  collections::{BTreeMap, HashMap},
+  path::PathBuf,
+};
 
 #[derive(Debug)]
 pub struct Graph {
-  pub adjacency_list: HashMap<PathBuf, Vec<PathBuf>>,
+  pub adjacency_list: BTreeMap<PathBuf, Vec<PathBuf>>,
 }
 
 impl Graph {
   pub fn new() -> Self {
     Self {
-      adjacency_list: HashMap::new(),
+      adjacency_list: BTreeMap::new(),
     }
   }
 
@@ -20,6 +23,12 @@ impl Graph {
       .push(edge);
   }
 
+  pub fn remove_edge(&mut self, node: &PathBuf, edge: &PathBuf) {
+    if let Some(neighbors) = self.adjacency_list.get_mut(node) {
+      neighbors.retain(|neighbor| neighbor != edge);
+    }
+  }
+
   pub fn get_neighbors(&self, node: &PathBuf) -> Option<&Vec<PathBuf>> {
     self.adjacency_list.get(node)
   }
@@ -0,0 +1,209 @@
+use crate::preprocessor::preprocess::parse_directive;
+
+/// The include guards of a file: a `#pragma once` and/or a `#ifndef X / #define X / #endif`
+/// block wrapping the whole file. Lines are 0-based indices into the file contents.
+#[derive(Clone, Debug, Default)]
+pub struct IncludeGuard {
+  pub pragma_once: Option<usize>,
+  pub guard_macro: Option<GuardMacro>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GuardMacro {
+  pub name: String,
+  pub lines: [usize; 3],
+}
+
+impl IncludeGuard {
+  pub fn is_guarded(&self) -> bool {
+    self.pragma_once.is_some() || self.guard_macro.is_some()
+  }
+
+  pub fn macro_name(&self) -> Option<&str> {
+    self.guard_macro.as_ref().map(|guard| guard.name.as_str())
+  }
+}
+
+/// Gets the indices of the lines that are not blank and not only made of comments.
+fn significant_lines(contents: &str) -> Vec<(usize, &str)> {
+  let mut lines = Vec::new();
+  let mut in_block_comment = false;
+
+  for (i, line) in contents.lines().enumerate() {
+    let mut rest = line.trim();
+
+    loop {
+      if in_block_comment {
+        match rest.find("*/") {
+          Some(end) => {
+            in_block_comment = false;
+            rest = rest[end + 2..].trim_start();
+          }
+          None => {
+            rest = "";
+            break;
+          }
+        }
+      } else if let Some(comment) = rest.strip_prefix("/*") {
+        in_block_comment = true;
+        rest = comment;
+      } else {
+        break;
+      }
+    }
+
+    if !rest.is_empty() && !rest.starts_with("//") {
+      lines.push((i, rest));
+    }
+  }
+
+  lines
+}
+
+/// Gets the macro guarded by `#ifndef X`, `#if !defined(X)` or `#if !defined X`.
+fn guarded_macro(line: &str) -> Option<&str> {
+  match parse_directive(line)? {
+    ("ifndef", name) => Some(name),
+    ("if", condition) => {
+      let condition = condition.strip_prefix('!')?.trim_start();
+      let name = condition.strip_prefix("defined")?.trim();
+      let name = match name.strip_prefix('(') {
+        Some(name) => name.strip_suffix(')')?.trim(),
+        None => name,
+      };
+      Some(name)
+    }
+    _ => None,
+  }
+}
+
+pub fn detect_include_guard(contents: &str) -> IncludeGuard {
+  let lines = significant_lines(contents);
+
+  let pragma_once = lines
+    .iter()
+    .find(|(_, line)| parse_directive(line) == Some(("pragma", "once")))
+    .map(|(i, _)| *i);
+
+  // Only the significant lines that are not the `#pragma once` matter for the guard macro
+  let lines = lines
+    .into_iter()
+    .filter(|(i, _)| Some(*i) != pragma_once)
+    .collect::<Vec<(usize, &str)>>();
+
+  IncludeGuard {
+    pragma_once,
+    guard_macro: detect_guard_macro(&lines),
+  }
+}
+
+fn detect_guard_macro(lines: &[(usize, &str)]) -> Option<GuardMacro> {
+  if lines.len() < 3 {
+    return None;
+  }
+
+  let (first, first_line) = lines.first()?;
+  let (second, second_line) = lines.get(1)?;
+  let (last, last_line) = lines.last()?;
+
+  let name = guarded_macro(first_line)?;
+
+  match parse_directive(second_line)? {
+    ("define", defined) if defined.split_whitespace().next() == Some(name) => {}
+    _ => return None,
+  }
+
+  if parse_directive(last_line)?.0 != "endif" {
+    return None;
+  }
+
+  // The `#endif` on the last line must close the `#ifndef` on the first one, which must not have
+  // other branches: they would be left without their `#ifndef` once the guard is removed
+  let mut depth = 0;
+  for (i, line) in lines {
+    match parse_directive(line).map(|(directive, _)| directive) {
+      Some("if") | Some("ifdef") | Some("ifndef") => depth += 1,
+      Some("else") | Some("elif") if depth == 1 => return None,
+      Some("endif") => {
+        depth -= 1;
+        if depth == 0 && i != last {
+          return None;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  Some(GuardMacro {
+    name: name.to_string(),
+    lines: [*first, *second, *last],
+  })
+}
+
+/// Removes the include guards from the file. They are redundant once the file has been combined,
+/// as every file is only included once.
+pub fn strip_include_guard(contents: &str, guard: &IncludeGuard) -> String {
+  let mut removed = Vec::new();
+  removed.extend(guard.pragma_once);
+  if let Some(guard_macro) = &guard.guard_macro {
+    removed.extend(guard_macro.lines);
+  }
+
+  let mut output = contents
+    .lines()
+    .enumerate()
+    .filter(|(i, _)| !removed.contains(i))
+    .map(|(_, line)| line)
+    .collect::<Vec<&str>>()
+    .join("\n");
+
+  if contents.ends_with('\n') {
+    output.push('\n');
+  }
+
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{detect_include_guard, strip_include_guard};
+
+  #[test]
+  fn detects_the_guard_macro() {
+    let contents = "// Noise\n#ifndef NOISE_GLSL\n#define NOISE_GLSL\n#ifdef HQ\nfloat a;\n#else\nfloat b;\n#endif\n#endif\n";
+    let guard = detect_include_guard(contents);
+
+    assert_eq!(guard.macro_name(), Some("NOISE_GLSL"));
+    assert_eq!(
+      strip_include_guard(contents, &guard),
+      "// Noise\n#ifdef HQ\nfloat a;\n#else\nfloat b;\n#endif\n"
+    );
+  }
+
+  #[test]
+  fn detects_pragma_once() {
+    let guard = detect_include_guard("#pragma once\nfloat a;\n");
+
+    assert!(guard.is_guarded());
+    assert_eq!(guard.macro_name(), None);
+  }
+
+  #[test]
+  fn a_guard_with_other_branches_is_not_a_guard() {
+    for branch in ["#else", "#elif defined(OTHER)"] {
+      let contents = format!(
+        "#ifndef NOISE_GLSL\n#define NOISE_GLSL\nfloat a;\n{}\nfloat b;\n#endif\n",
+        branch
+      );
+
+      assert!(!detect_include_guard(&contents).is_guarded(), "{}", branch);
+    }
+  }
+
+  #[test]
+  fn the_endif_must_close_the_guard() {
+    let contents = "#ifndef A\n#define A\nfloat a;\n#endif\n#ifdef B\nfloat b;\n#endif\n";
+
+    assert!(!detect_include_guard(contents).is_guarded());
+  }
+}
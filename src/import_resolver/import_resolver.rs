@@ -1,12 +1,17 @@
+use crate::config::DefineValue;
 use crate::utils::log::{print_level, Level};
-use crate::{file_exists, log, log_with_color, logln};
+use crate::utils::path::to_forward_slashes;
+use crate::{canonicalize, file_exists, log, log_with_color, logln};
 use core::fmt;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::file_manager::FileManager;
 use super::graph::Graph;
+use super::guards::strip_include_guard;
+use super::packages;
+use super::selective_import::{self, Selection};
 
 #[derive(Debug, Clone)]
 pub enum DefinitionErrorType {
@@ -28,8 +33,19 @@ pub enum ImportError {
   CycleDetected(PathBuf, PathBuf),
   CouldNotParseFile(PathBuf),
   FileNotFound(PathBuf),
-  ImportNotFound(PathBuf, PathBuf),
+  ImportNotFound {
+    file: PathBuf,
+    include: String,
+    candidates: Vec<PathBuf>,
+    /// Set when the include was looked up in a npm package.
+    package: Option<String>,
+  },
   DuplicateImport(PathBuf, PathBuf),
+  SymbolNotFound {
+    file: PathBuf,
+    import: PathBuf,
+    name: String,
+  },
   DuplicateDefinition {
     name: String,
     first_file: PathBuf,
@@ -38,71 +54,127 @@ pub enum ImportError {
   },
 }
 
-pub fn try_resolve_imports(file: &PathBuf, input_folder_parent: &PathBuf) -> Option<String> {
-  match resolve_imports(file) {
+/// Shows `path` relative to the parent of the input folder when possible. Included files can
+/// live outside of it (e.g. in an include path), in which case the full path is shown. Files
+/// coming from npm packages are shown as `package: path/inside/package`.
+fn display_path(path: &Path, input_folder_parent: &Path) -> String {
+  if let Some((package, path)) = packages::get_package_of(path) {
+    return format!("{}: {}", package, to_forward_slashes(&path));
+  }
+
+  let path = path.strip_prefix(input_folder_parent).unwrap_or(path);
+  to_forward_slashes(path)
+}
+
+/// A shader with the files it includes combined into it.
+#[derive(Debug, Clone)]
+pub struct CombinedShader {
+  pub source: String,
+  /// The names of the included files, in the order they were combined, without the shader.
+  pub includes: Vec<String>,
+}
+
+pub fn try_resolve_imports(
+  file: &PathBuf,
+  input_folder_parent: &PathBuf,
+  include_paths: &[PathBuf],
+  defines: &[(String, DefineValue)],
+) -> Option<CombinedShader> {
+  match resolve_imports(file, include_paths, defines) {
     Ok(output) => Some(output),
     Err(err) => {
       match err {
-        ImportError::ImportNotFound(file_path, import_path) => {
-          let file_path = file_path.strip_prefix(input_folder_parent).unwrap();
-          let import_path = import_path.strip_prefix(input_folder_parent).unwrap();
+        ImportError::ImportNotFound {
+          file,
+          include,
+          candidates,
+          package,
+        } => {
           print_level(Level::ERROR);
           log("The file ");
-          log_with_color(import_path.to_str().unwrap(), "blue");
+          log_with_color(&display_path(&file, input_folder_parent), "blue");
           log(" is trying to import a file that does not exist: ");
-          log_with_color(file_path.to_str().unwrap(), "blue");
+          log_with_color(&include, "blue");
+
+          if let Some(package) = package {
+            log(" from the package ");
+            log_with_color(&package, "blue");
+          }
+
           logln("");
+
+          if candidates.is_empty() {
+            logln("\tNo include paths are configured, see `include_paths` or --include.");
+          } else {
+            logln("\tTried:");
+          }
+
+          for candidate in candidates {
+            log("\t  ");
+            log_with_color(&display_path(&candidate, input_folder_parent), "blue");
+            logln("");
+          }
         }
         ImportError::CouldNotParseFile(file_path) => {
-          let file_path = file_path.strip_prefix(input_folder_parent).unwrap();
+          let file_path = display_path(&file_path, input_folder_parent);
           print_level(Level::ERROR);
           log("Could not parse file: ");
-          log_with_color(file_path.to_str().unwrap(), "blue");
+          log_with_color(&file_path, "blue");
           logln("");
         }
         ImportError::CycleDetected(file_path, import_path) => {
-          let file_path = file_path.strip_prefix(input_folder_parent).unwrap();
-          let import_path = import_path.strip_prefix(input_folder_parent).unwrap();
+          let file_path = display_path(&file_path, input_folder_parent);
+          let import_path = display_path(&import_path, input_folder_parent);
           print_level(Level::ERROR);
           log("Cycle detected between files: ");
-          log_with_color(file_path.to_str().unwrap(), "blue");
+          log_with_color(&file_path, "blue");
           log(" and ");
-          log_with_color(import_path.to_str().unwrap(), "blue");
+          log_with_color(&import_path, "blue");
           logln("");
         }
         ImportError::FileNotFound(file_path) => {
-          let file_path = file_path.strip_prefix(input_folder_parent).unwrap();
+          let file_path = display_path(&file_path, input_folder_parent);
           print_level(Level::ERROR);
           log("File not found: ");
-          log_with_color(file_path.to_str().unwrap(), "blue");
+          log_with_color(&file_path, "blue");
           logln("");
         }
         ImportError::DuplicateImport(file_path, import_path) => {
-          let file_path = file_path.strip_prefix(input_folder_parent).unwrap();
-          let import_path = import_path.strip_prefix(input_folder_parent).unwrap();
+          let file_path = display_path(&file_path, input_folder_parent);
+          let import_path = display_path(&import_path, input_folder_parent);
           print_level(Level::ERROR);
           log("Duplicate import of file: ");
-          log_with_color(import_path.to_str().unwrap(), "blue");
+          log_with_color(&import_path, "blue");
           log(" in file: ");
-          log_with_color(file_path.to_str().unwrap(), "blue");
+          log_with_color(&file_path, "blue");
           logln("");
         }
+        ImportError::SymbolNotFound { file, import, name } => {
+          print_level(Level::ERROR);
+          log("The file ");
+          log_with_color(&display_path(&file, input_folder_parent), "blue");
+          log(" imports ");
+          log_with_color(&name, "blue");
+          log(" from ");
+          log_with_color(&display_path(&import, input_folder_parent), "blue");
+          logln(", which does not define it.");
+        }
         ImportError::DuplicateDefinition {
           name,
           first_file,
           second_file,
           definition_type,
         } => {
-          let first_file = first_file.strip_prefix(input_folder_parent).unwrap();
-          let second_file = second_file.strip_prefix(input_folder_parent).unwrap();
+          let first_file = display_path(&first_file, input_folder_parent);
+          let second_file = display_path(&second_file, input_folder_parent);
 
           print_level(Level::ERROR);
           log(&format!("Duplicate definition of {} ", definition_type));
           log_with_color(&name, "blue");
           log(" in files: ");
-          log_with_color(first_file.to_str().unwrap(), "blue");
+          log_with_color(&first_file, "blue");
           log(" and ");
-          log_with_color(second_file.to_str().unwrap(), "blue");
+          log_with_color(&second_file, "blue");
           logln("");
         }
       }
@@ -112,29 +184,129 @@ pub fn try_resolve_imports(file: &PathBuf, input_folder_parent: &PathBuf) -> Opt
   }
 }
 
-pub fn resolve_imports(file: &PathBuf) -> Result<String, ImportError> {
-  let mut resolver = ImportResolver::new();
+pub fn resolve_imports(
+  file: &PathBuf,
+  include_paths: &[PathBuf],
+  defines: &[(String, DefineValue)],
+) -> Result<CombinedShader, ImportError> {
+  let mut resolver = ImportResolver::new(include_paths.to_vec());
+  resolver.selections.insert(file.clone(), Selection::All);
 
   resolver.build_import_graph(file)?;
   let output = resolver.combine_files(file, &mut HashSet::new());
   resolver.check_for_duplicate_definitions()?;
   let output = move_glsl_version_to_top(output);
+  let output = insert_defines(&output, defines);
   let output = comment_include(output);
-  return Ok(output);
+
+  let includes = resolver
+    .combined_files
+    .iter()
+    .filter(|path| *path != file)
+    .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
+    .collect();
+
+  return Ok(CombinedShader {
+    source: output,
+    includes,
+  });
 }
 
 #[derive(Debug)]
 struct ImportResolver {
   graph: Graph,
   file_manager: FileManager,
+  /// Folders searched in order for `#include <...>`, and for `#include "..."` when the file
+  /// is not found next to the including file. Packages in node_modules are searched after them.
+  include_paths: Vec<PathBuf>,
+  /// Guard macros of the files combined so far.
+  included_guards: HashSet<String>,
+  /// Files left out of the output because a file with the same guard macro was already included.
+  skipped_files: HashSet<PathBuf>,
+  /// The symbols needed from every file, from the selective includes pointing to it.
+  selections: HashMap<PathBuf, Selection>,
+  /// The files in the output, in the order they were combined.
+  combined_files: Vec<PathBuf>,
 }
 
 impl ImportResolver {
-  pub fn new() -> Self {
+  pub fn new(include_paths: Vec<PathBuf>) -> Self {
     Self {
       graph: Graph::new(),
       file_manager: FileManager::new(),
+      include_paths,
+      included_guards: HashSet::new(),
+      skipped_files: HashSet::new(),
+      selections: HashMap::new(),
+      combined_files: Vec::new(),
+    }
+  }
+
+  fn resolve_include(
+    &self,
+    file_path: &Path,
+    include: &glsl::syntax::Path,
+  ) -> Result<PathBuf, ImportError> {
+    let mut candidates = Vec::new();
+
+    let (include, is_relative) = match include {
+      glsl::syntax::Path::Absolute(include) => (include, false),
+      glsl::syntax::Path::Relative(include) => {
+        let parent_path = file_path.parent().unwrap();
+        candidates.push(parent_path.join(include));
+        (include, true)
+      }
+    };
+
+    if Path::new(include).is_absolute() {
+      candidates.push(PathBuf::from(include));
+    } else {
+      for include_path in &self.include_paths {
+        candidates.push(include_path.join(include));
+      }
+    }
+
+    let mut candidates = candidates
+      .iter()
+      .map(|candidate| PathBuf::from(canonicalize(candidate.to_str().unwrap())))
+      .collect::<Vec<PathBuf>>();
+
+    if let Some(path) = candidates
+      .iter()
+      .find(|candidate| file_exists(candidate.to_str().unwrap()))
+    {
+      return Ok(path.clone());
+    }
+
+    // Quoted includes are paths relative to the including file, which are never packages
+    if is_relative {
+      return Err(ImportError::ImportNotFound {
+        file: file_path.to_path_buf(),
+        include: include.clone(),
+        candidates,
+        package: None,
+      });
+    }
+
+    // Includes like `<@scope/package/file.glsl>` are looked up in node_modules last
+    let parent_path = PathBuf::from(canonicalize(file_path.parent().unwrap().to_str().unwrap()));
+    let package = packages::resolve_package_include(&parent_path, include);
+    let package_name = package.as_ref().map(|package| package.name.clone());
+
+    if let Some(package) = package {
+      if let Some(path) = package.path {
+        return Ok(PathBuf::from(canonicalize(path.to_str().unwrap())));
+      }
+
+      candidates.extend(package.candidates);
     }
+
+    Err(ImportError::ImportNotFound {
+      file: file_path.to_path_buf(),
+      include: include.clone(),
+      candidates,
+      package: package_name,
+    })
   }
 
   fn check_for_duplicate_definitions(&self) -> Result<(), ImportError> {
@@ -142,8 +314,22 @@ impl ImportResolver {
     let mut fn_definitions: HashMap<String, PathBuf> = HashMap::new();
     let mut struct_definitions: HashMap<String, PathBuf> = HashMap::new();
 
-    for (file_path, file) in &self.file_manager.files {
-      for function in &file.functions {
+    // Only the files in the output, and the parts of them that are selected, can clash
+    for file_path in &self.combined_files {
+      let file = &self.file_manager.files[file_path];
+      let selected = match self.selections.get(file_path) {
+        Some(Selection::Symbols(symbols)) => {
+          Some(selective_import::selected_symbols(&file.ast, symbols))
+        }
+        _ => None,
+      };
+      let is_selected = |name: &String| {
+        selected
+          .as_ref()
+          .is_none_or(|selected| selected.contains(name))
+      };
+
+      for function in file.functions.iter().filter(|name| is_selected(name)) {
         if let Some(file_owner) = fn_definitions.get(function) {
           // GLSL supports function overloading, but we will only allow it
           // if the functions are defined in the same file.
@@ -160,7 +346,7 @@ impl ImportResolver {
         fn_definitions.insert(function.clone(), file_path.clone());
       }
 
-      for struct_name in &file.structs {
+      for struct_name in file.structs.iter().filter(|name| is_selected(name)) {
         if let Some(file_owner) = struct_definitions.get(struct_name) {
           // Here we do not check if the struct is defined in the same file
           // because GLSL does not support struct overloading.
@@ -180,16 +366,66 @@ impl ImportResolver {
   }
 
   fn build_import_graph(&mut self, file_path: &PathBuf) -> Result<&Graph, ImportError> {
-    let file_imports = self.file_manager.get_file_imports(&file_path)?;
+    let file = self.file_manager.get_file(file_path)?;
+    let mut file_imports: Vec<PathBuf> = Vec::new();
+
+    for include in &file.includes {
+      let path = self.resolve_include(file_path, &include.path)?;
+
+      if let Selection::Symbols(symbols) = &include.selection {
+        let imported_file = self.file_manager.get_file(&path)?;
+
+        if let Some(name) = selective_import::missing_symbols(&imported_file.ast, symbols).first() {
+          return Err(ImportError::SymbolNotFound {
+            file: file_path.clone(),
+            import: path,
+            name: name.clone(),
+          });
+        }
+      }
+
+      self
+        .selections
+        .entry(path.clone())
+        .and_modify(|selection| selection.merge(&include.selection))
+        .or_insert_with(|| include.selection.clone());
+
+      if file_imports.contains(&path) {
+        // Including a guarded file twice is harmless, the second include is a no-op. Selective
+        // includes of the same file are combined.
+        if self.file_manager.get_file(&path)?.guard.is_guarded()
+          || include.selection != Selection::All
+        {
+          continue;
+        }
+
+        return Err(ImportError::DuplicateImport(file_path.clone(), path));
+      }
+
+      file_imports.push(path);
+    }
 
     for path in file_imports {
-      if !file_exists(path.to_str().unwrap()) {
-        return Err(ImportError::ImportNotFound(file_path.clone(), path.clone()));
+      if self
+        .graph
+        .get_neighbors(file_path)
+        .is_some_and(|neighbors| neighbors.contains(&path))
+      {
+        // The edge from this file is already in the graph, which happens when this file was
+        // walked before because another file includes it too. Its includes were walked then
+        continue;
       }
 
       self.graph.add_edge(file_path.clone(), path.clone());
 
       if self.graph.has_cycle() {
+        // The guard of a file stops it from being included again while it is being included,
+        // so the include closing the cycle does nothing
+        if self.file_manager.get_file(&path)?.guard.is_guarded() {
+          self.graph.remove_edge(file_path, &path);
+          continue;
+        }
+
         return Err(ImportError::CycleDetected(file_path.clone(), path.clone()));
       }
 
@@ -210,13 +446,38 @@ impl ImportResolver {
 
     let file = self.file_manager.get_file(node).unwrap();
 
-    for neighbor in file.imports {
+    if let Some(guard_macro) = file.guard.macro_name() {
+      // Another file with the same guard was already included, like the preprocessor would we
+      // skip this one (e.g. two copies of the same library)
+      if !self.included_guards.insert(guard_macro.to_string()) {
+        self.skipped_files.insert(node.clone());
+        return output;
+      }
+    }
+
+    let neighbors = self.graph.get_neighbors(node).cloned().unwrap_or_default();
+
+    for neighbor in neighbors {
       output += &self.combine_files(&neighbor, visited);
     }
 
     let file_name = node.file_name().unwrap().to_str().unwrap();
+    self.combined_files.push(node.clone());
+
+    if let Some(Selection::Symbols(symbols)) = self.selections.get(node) {
+      let symbols_list = symbols.iter().cloned().collect::<Vec<String>>().join(", ");
+      output += &format!("\n// File: {} ({})\n", file_name, symbols_list);
+      output += &selective_import::emit_selection(&file.ast, symbols);
+      return output;
+    }
+
     output += &format!("\n// File: {}\n", file_name);
-    output += &file.contents;
+
+    if file.guard.is_guarded() {
+      output += &strip_include_guard(&file.contents, &file.guard);
+    } else {
+      output += &file.contents;
+    }
 
     return output;
   }
@@ -249,6 +510,34 @@ fn move_glsl_version_to_top(content: String) -> String {
   return lines.join("\n");
 }
 
+/// Inserts a `#define` for every define right after the `#version` line, which has to stay the
+/// first one, or at the top if there is none.
+pub fn insert_defines(content: &str, defines: &[(String, DefineValue)]) -> String {
+  let defines = defines
+    .iter()
+    .filter_map(|(name, value)| Some(format!("#define {} {}", name, value.to_macro_value()?)))
+    .collect::<Vec<String>>();
+
+  if defines.is_empty() {
+    return content.to_string();
+  }
+
+  let mut lines = content.lines().collect::<Vec<&str>>();
+  let version_line = lines.iter().position(|line| {
+    let parts = line.split_whitespace().collect::<Vec<&str>>();
+    parts.first() == Some(&"#version") || parts.starts_with(&["#", "version"])
+  });
+
+  let index = version_line.map_or(0, |line| line + 1);
+  lines.splice(index..index, defines.iter().map(String::as_str));
+
+  let mut output = lines.join("\n");
+  if content.ends_with('\n') {
+    output.push('\n');
+  }
+  output
+}
+
 fn comment_include(content: String) -> String {
   let lines = content.lines();
   let mut new_lines = Vec::new();
@@ -256,7 +545,9 @@ fn comment_include(content: String) -> String {
   for line in lines {
     let parts = line.trim().split_whitespace().collect::<Vec<&str>>();
 
-    if parts.len() >= 2 && ((parts[0] == "#" && parts[1] == "include") || parts[0] == "#include") {
+    if (parts.len() >= 2 && ((parts[0] == "#" && parts[1] == "include") || parts[0] == "#include"))
+      || selective_import::is_selective_include(line)
+    {
       new_lines.push(format!("// {}", line));
     } else {
       new_lines.push(line.to_string());
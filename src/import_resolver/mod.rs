@@ -1,4 +1,7 @@
 pub mod file;
 pub mod file_manager;
 pub mod graph;
+pub mod guards;
 pub mod import_resolver;
+pub mod packages;
+pub mod selective_import;
@@ -0,0 +1,268 @@
+use crate::{file_exists, read_file};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug)]
+pub struct PackageResolution {
+  /// The name of the package, e.g. `@our-org/glsl-noise`.
+  pub name: String,
+  /// Every path that was tried, in order. When the package itself could not be found these are
+  /// the package folders that were looked for instead.
+  pub candidates: Vec<PathBuf>,
+  pub path: Option<PathBuf>,
+}
+
+/// Splits an include such as `@scope/name/file.glsl` or `name/file.glsl` into the package name
+/// and the path inside of the package.
+fn split_package_include(include: &str) -> Option<(String, String)> {
+  if include.starts_with('.') || Path::new(include).is_absolute() {
+    return None;
+  }
+
+  let (name, subpath) = if include.starts_with('@') {
+    let mut parts = include.splitn(3, '/');
+    let name = format!("{}/{}", parts.next()?, parts.next()?);
+    (name, parts.next()?.to_string())
+  } else {
+    let (name, subpath) = include.split_once('/')?;
+    (name.to_string(), subpath.to_string())
+  };
+
+  if name.is_empty() || subpath.is_empty() {
+    return None;
+  }
+
+  Some((name, subpath))
+}
+
+/// Resolves an include from a npm package with Node-style lookup: the `node_modules` folders of
+/// `from` and of all of its ancestors are searched for the package, and the closest one wins.
+///
+/// Inside of the package the `glsl` field of its `package.json` (a folder containing the shaders)
+/// is used first, then its `exports`, and then the path relative to the package root.
+pub fn resolve_package_include(from: &Path, include: &str) -> Option<PackageResolution> {
+  let (name, subpath) = split_package_include(include)?;
+  let mut package_folders = Vec::new();
+
+  for directory in from.ancestors() {
+    let package_root = directory.join("node_modules").join(&name);
+
+    if !file_exists(package_root.to_str().unwrap()) {
+      package_folders.push(package_root);
+      continue;
+    }
+
+    let candidates = package_candidates(&package_root, &subpath);
+    let path = candidates
+      .iter()
+      .find(|candidate| file_exists(candidate.to_str().unwrap()))
+      .cloned();
+
+    return Some(PackageResolution {
+      name,
+      candidates,
+      path,
+    });
+  }
+
+  Some(PackageResolution {
+    name,
+    candidates: package_folders,
+    path: None,
+  })
+}
+
+fn package_candidates(package_root: &Path, subpath: &str) -> Vec<PathBuf> {
+  let package_json_path = package_root.join("package.json");
+
+  let package_json = if file_exists(package_json_path.to_str().unwrap()) {
+    serde_json::from_str::<Value>(&read_file(package_json_path.to_str().unwrap().to_string()))
+      .unwrap_or(Value::Null)
+  } else {
+    Value::Null
+  };
+
+  let mut candidates = Vec::new();
+
+  if let Some(glsl_folder) = package_json.get("glsl").and_then(Value::as_str) {
+    candidates.push(package_root.join(glsl_folder).join(subpath));
+  }
+
+  match package_json.get("exports") {
+    // With `exports` only the exported paths are visible, like in Node
+    Some(exports) => {
+      if let Some(target) = resolve_exports(exports, &format!("./{}", subpath)) {
+        candidates.push(package_root.join(target));
+      }
+    }
+    None => candidates.push(package_root.join(subpath)),
+  }
+
+  candidates
+}
+
+/// Finds the target of `subpath` (starting with `./`) in an `exports` field. Both exact entries
+/// and patterns with a single `*` are supported.
+fn resolve_exports(exports: &Value, subpath: &str) -> Option<String> {
+  let exports = exports.as_object()?;
+
+  if let Some(target) = exports.get(subpath) {
+    return resolve_export_target(target);
+  }
+
+  // Like in Node, the pattern with the longest prefix wins
+  let (prefix, suffix, target) = exports
+    .iter()
+    .filter_map(|(pattern, target)| {
+      let (prefix, suffix) = pattern.split_once('*')?;
+      let matches = subpath.len() >= prefix.len() + suffix.len()
+        && subpath.starts_with(prefix)
+        && subpath.ends_with(suffix);
+      matches.then_some((prefix, suffix, target))
+    })
+    .max_by_key(|(prefix, _, _)| prefix.len())?;
+
+  let matched = &subpath[prefix.len()..subpath.len() - suffix.len()];
+  resolve_export_target(target).map(|target| target.replace('*', matched))
+}
+
+/// Picks the target of an export, preferring the `glsl` condition over `default`.
+fn resolve_export_target(target: &Value) -> Option<String> {
+  match target {
+    Value::String(target) => Some(target.clone()),
+    Value::Array(targets) => targets.iter().find_map(resolve_export_target),
+    Value::Object(conditions) => ["glsl", "default"]
+      .iter()
+      .find_map(|condition| conditions.get(*condition).and_then(resolve_export_target)),
+    _ => None,
+  }
+}
+
+/// Gets the package a file belongs to, and the path of the file inside of it, when the file
+/// lives in a `node_modules` folder. Package folders themselves are not considered files.
+pub fn get_package_of(path: &Path) -> Option<(String, PathBuf)> {
+  let components = path.components().collect::<Vec<Component>>();
+  let node_modules = components
+    .iter()
+    .rposition(|component| component.as_os_str() == "node_modules")?;
+
+  let mut rest = components[node_modules + 1..]
+    .iter()
+    .map(|component| component.as_os_str().to_str().unwrap());
+
+  let first = rest.next()?;
+  let name = if first.starts_with('@') {
+    format!("{}/{}", first, rest.next()?)
+  } else {
+    first.to_string()
+  };
+
+  let path = rest.collect::<PathBuf>();
+  if path.as_os_str().is_empty() {
+    return None;
+  }
+
+  Some((name, path))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{get_package_of, resolve_exports, split_package_include};
+  use serde_json::json;
+  use std::path::{Path, PathBuf};
+
+  #[test]
+  fn splits_the_package_name() {
+    assert_eq!(
+      split_package_include("glsl-noise/simplex.glsl"),
+      Some(("glsl-noise".to_string(), "simplex.glsl".to_string()))
+    );
+    assert_eq!(
+      split_package_include("@our-org/glsl-noise/src/simplex.glsl"),
+      Some((
+        "@our-org/glsl-noise".to_string(),
+        "src/simplex.glsl".to_string()
+      ))
+    );
+  }
+
+  #[test]
+  fn paths_are_not_packages() {
+    assert_eq!(split_package_include("./simplex.glsl"), None);
+    assert_eq!(split_package_include("../lib/simplex.glsl"), None);
+    assert_eq!(split_package_include("/usr/share/simplex.glsl"), None);
+    assert_eq!(split_package_include("simplex.glsl"), None);
+    assert_eq!(split_package_include("@our-org/glsl-noise"), None);
+  }
+
+  #[test]
+  fn resolves_exact_exports_first() {
+    let exports = json!({
+      "./simplex.glsl": "./dist/simplex.glsl",
+      "./*": "./src/*",
+    });
+
+    assert_eq!(
+      resolve_exports(&exports, "./simplex.glsl"),
+      Some("./dist/simplex.glsl".to_string())
+    );
+    assert_eq!(
+      resolve_exports(&exports, "./perlin.glsl"),
+      Some("./src/perlin.glsl".to_string())
+    );
+  }
+
+  #[test]
+  fn the_longest_export_pattern_wins() {
+    let exports = json!({
+      "./*": "./src/*",
+      "./noise/*.glsl": "./shaders/noise/*.glsl",
+    });
+
+    assert_eq!(
+      resolve_exports(&exports, "./noise/simplex.glsl"),
+      Some("./shaders/noise/simplex.glsl".to_string())
+    );
+    assert_eq!(
+      resolve_exports(&json!({ "./a.glsl": "./a.glsl" }), "./b.glsl"),
+      None
+    );
+  }
+
+  #[test]
+  fn prefers_the_glsl_condition() {
+    let exports = json!({
+      "./*": { "import": "./esm/*", "glsl": "./glsl/*", "default": "./lib/*" },
+      "./main.glsl": [{ "node": "./node.glsl" }, "./main.glsl"],
+    });
+
+    assert_eq!(
+      resolve_exports(&exports, "./simplex.glsl"),
+      Some("./glsl/simplex.glsl".to_string())
+    );
+    assert_eq!(
+      resolve_exports(&exports, "./main.glsl"),
+      Some("./main.glsl".to_string())
+    );
+  }
+
+  #[test]
+  fn finds_the_package_of_a_file() {
+    assert_eq!(
+      get_package_of(Path::new(
+        "/app/node_modules/@our-org/glsl-noise/src/simplex.glsl"
+      )),
+      Some((
+        "@our-org/glsl-noise".to_string(),
+        PathBuf::from("src/simplex.glsl")
+      ))
+    );
+    // The closest node_modules folder wins
+    assert_eq!(
+      get_package_of(Path::new("/app/node_modules/a/node_modules/b/b.glsl")),
+      Some(("b".to_string(), PathBuf::from("b.glsl")))
+    );
+    assert_eq!(get_package_of(Path::new("/app/node_modules/a")), None);
+    assert_eq!(get_package_of(Path::new("/app/shaders/a.glsl")), None);
+  }
+}
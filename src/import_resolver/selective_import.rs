@@ -0,0 +1,140 @@
+use crate::utils::symbols::{declared_symbols, reachable_declarations};
+use crate::utils::transpile::show_external_declaration;
+use glsl::syntax::{ExternalDeclaration, TranslationUnit};
+use std::collections::BTreeSet;
+
+/// The symbols requested from an included file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selection {
+  All,
+  Symbols(BTreeSet<String>),
+}
+
+impl Selection {
+  /// Combines the selections of a file included from several places.
+  pub fn merge(&mut self, other: &Selection) {
+    match (&mut *self, other) {
+      (Selection::Symbols(symbols), Selection::Symbols(other)) => {
+        symbols.extend(other.iter().cloned())
+      }
+      _ => *self = Selection::All,
+    }
+  }
+}
+
+fn parse_symbol_list(list: &str) -> Option<Vec<String>> {
+  let symbols = list
+    .split(',')
+    .map(|symbol| symbol.trim().to_string())
+    .collect::<Vec<String>>();
+
+  let is_identifier = |symbol: &String| {
+    symbol
+      .chars()
+      .next()
+      .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+      && symbol
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+  };
+
+  symbols.iter().all(is_identifier).then_some(symbols)
+}
+
+fn is_path(path: &str) -> bool {
+  (path.starts_with('"') && path.ends_with('"')) || (path.starts_with('<') && path.ends_with('>'))
+}
+
+/// Parses `#include "path" : a, b` and `#import { a, b } from "path"` into the path (with its
+/// quotes or angle brackets) and the requested symbols.
+fn parse_selective_include(line: &str) -> Option<(&str, Vec<String>)> {
+  let directive = line.trim().strip_prefix('#')?.trim_start();
+
+  if let Some(rest) = directive.strip_prefix("include") {
+    let (path, symbols) = rest.split_once(':')?;
+    let path = path.trim();
+    return is_path(path).then_some((path, parse_symbol_list(symbols)?));
+  }
+
+  let rest = directive.strip_prefix("import")?.trim_start();
+  let (symbols, path) = rest.strip_prefix('{')?.split_once('}')?;
+  let path = path.trim_start().strip_prefix("from")?.trim();
+  is_path(path).then_some((path, parse_symbol_list(symbols)?))
+}
+
+/// Rewrites the selective includes of a file into plain `#include` directives that the glsl
+/// parser understands. Returns the rewritten source and the selection of every include
+/// directive of the file, in order.
+pub fn extract_selective_includes(source: &str) -> (String, Vec<Selection>) {
+  let mut selections = Vec::new();
+  let mut lines = Vec::new();
+
+  for line in source.lines() {
+    if let Some((path, symbols)) = parse_selective_include(line) {
+      selections.push(Selection::Symbols(symbols.into_iter().collect()));
+      lines.push(format!("#include {}", path));
+      continue;
+    }
+
+    let directive = line.trim().strip_prefix('#').map(str::trim_start);
+    if directive.is_some_and(|directive| directive.starts_with("include")) {
+      selections.push(Selection::All);
+    }
+
+    lines.push(line.to_string());
+  }
+
+  (lines.join("\n"), selections)
+}
+
+pub fn is_selective_include(line: &str) -> bool {
+  parse_selective_include(line).is_some()
+}
+
+/// Gets the symbols of `symbols` that the file does not define.
+pub fn missing_symbols(ast: &TranslationUnit, symbols: &BTreeSet<String>) -> Vec<String> {
+  let declared = ast
+    .0
+     .0
+    .iter()
+    .flat_map(declared_symbols)
+    .collect::<BTreeSet<String>>();
+
+  symbols.difference(&declared).cloned().collect()
+}
+
+/// Gets the parts of a file needed by the selected symbols: their declarations and every struct,
+/// function, constant and macro they transitively reference.
+fn selected_declarations<'a>(
+  ast: &'a TranslationUnit,
+  symbols: &BTreeSet<String>,
+) -> Vec<&'a ExternalDeclaration> {
+  let declarations = &ast.0 .0;
+  let roots = symbols.iter().cloned().collect::<Vec<String>>();
+  let reachable = reachable_declarations(declarations, &roots, |_| false);
+
+  declarations
+    .iter()
+    .zip(reachable)
+    .filter(|(_, reachable)| *reachable)
+    .map(|(declaration, _)| declaration)
+    .collect()
+}
+
+/// Gets the names declared by the parts of a file that are emitted for the selected symbols.
+pub fn selected_symbols(ast: &TranslationUnit, symbols: &BTreeSet<String>) -> BTreeSet<String> {
+  selected_declarations(ast, symbols)
+    .into_iter()
+    .flat_map(declared_symbols)
+    .collect()
+}
+
+/// Re-emits the parts of a file needed by the selected symbols.
+pub fn emit_selection(ast: &TranslationUnit, symbols: &BTreeSet<String>) -> String {
+  let mut output = String::new();
+  for declaration in selected_declarations(ast, symbols) {
+    show_external_declaration(&mut output, declaration);
+  }
+
+  output
+}
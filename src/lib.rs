@@ -2,11 +2,16 @@
 
 use wasm_bindgen::prelude::*;
 
-mod cli;
-mod generator;
+pub mod cli;
+pub mod config;
+pub mod generator;
 mod import_resolver;
+mod preprocessor;
+mod transform;
 mod utils;
 
+pub use utils::get_shader_type::ShaderType;
+
 #[wasm_bindgen]
 extern "C" {
   fn read_file(file: String) -> String;
@@ -19,18 +24,98 @@ extern "C" {
   fn write_file(path: &str, content: &str);
 }
 
+/// `defines` are `NAME` or `NAME=VALUE` defines inserted after the `#version` of the shader.
+#[wasm_bindgen]
+pub fn start_cli(
+  file_path: String,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+  defines: Vec<String>,
+) {
+  cli::generate(file_path, input_folder, output_folder, options, defines);
+}
+
+/// Generates every shader of `files`, the shader files of the input folder, and their index.
+/// `defines` are the same as in `start_cli`.
+#[wasm_bindgen]
+pub fn start_batch(
+  files: Vec<String>,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+  defines: Vec<String>,
+) {
+  cli::generate_batch(files, input_folder, output_folder, options, defines);
+}
+
+/// Checks that the outputs of `files` do not overwrite each other, printing the collisions.
+#[wasm_bindgen]
+pub fn check_outputs(files: Vec<String>, input_folder: String, options: String) -> bool {
+  cli::check_outputs(&files, &input_folder, &options)
+}
+
+/// `files` are the shader files of the input folder, listed in the index of the output folder.
 #[wasm_bindgen]
-pub fn start_cli(file_path: String, input_folder: String, output_folder: String) {
-  cli::generate(file_path, input_folder, output_folder);
+pub fn write_index(
+  files: Vec<String>,
+  input_folder: String,
+  output_folder: String,
+  options: String,
+) {
+  cli::write_index(files, input_folder, output_folder, options);
 }
 
+/// Checks the options and the config file they point to, printing their errors.
 #[wasm_bindgen]
-pub fn resolve_imports(file: String, input_folder: String) -> String {
+pub fn check_options(options: String) -> bool {
+  cli::load_config(&options).is_some()
+}
+
+/// Invalid options or config files are printed, and no file is a shader then.
+#[wasm_bindgen]
+pub fn is_shader_file(file_path: String, options: String) -> bool {
+  let (options, config) = match cli::load_config(&options) {
+    Some(loaded) => loaded,
+    None => return false,
+  };
+  let file_path = std::path::PathBuf::from(file_path);
+  let settings = config.settings_for(&file_path, &options.settings);
+
+  utils::get_shader_type::get_shader_type(&file_path, &settings.stages()).is_some()
+}
+
+/// `options` are the same as in `start_cli`: the config file is the one they name, or the closest
+/// one, and its include paths and defines apply. `defines` are `NAME` or `NAME=VALUE` defines
+/// inserted after the `#version` of the shader. Errors are printed, and the result is empty then.
+#[wasm_bindgen]
+pub fn resolve_imports(
+  file: String,
+  input_folder: String,
+  options: String,
+  defines: Vec<String>,
+) -> String {
   let file = std::path::PathBuf::from(file);
   let input_folder = std::path::PathBuf::from(input_folder);
 
-  match import_resolver::import_resolver::try_resolve_imports(&file, &input_folder) {
-    Some(output) => output,
-    None => String::from(""),
+  let (mut options, config) = match cli::load_config(&options) {
+    Some(loaded) => loaded,
+    None => return String::from(""),
+  };
+
+  if let Err(error) = config::apply_define_flags(&mut options.settings, &defines) {
+    config::print_config_error(&error);
+    return String::from("");
   }
+
+  let settings = config.settings_for(&file, &options.settings);
+
+  import_resolver::import_resolver::try_resolve_imports(
+    &file,
+    &input_folder,
+    settings.include_paths(),
+    &settings.defines(),
+  )
+  .map(|combined| combined.source)
+  .unwrap_or_default()
 }
@@ -0,0 +1,225 @@
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  Number(i64),
+  Identifier(String),
+  Operator(&'static str),
+}
+
+/// Operators sorted so that the longest ones are matched first.
+const OPERATORS: [&str; 22] = [
+  "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|",
+  "!", "~", "(", ")",
+];
+
+fn parse_number(literal: &str) -> Result<i64, String> {
+  // Integer suffixes such as `1u` do not change the value
+  let digits = literal.trim_end_matches(['u', 'U']);
+
+  let value = if let Some(hex) = digits
+    .strip_prefix("0x")
+    .or_else(|| digits.strip_prefix("0X"))
+  {
+    i64::from_str_radix(hex, 16)
+  } else if digits.len() > 1 && digits.starts_with('0') {
+    i64::from_str_radix(&digits[1..], 8)
+  } else {
+    digits.parse::<i64>()
+  };
+
+  value.map_err(|_| format!("invalid number \"{}\"", literal))
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = Vec::new();
+  let mut rest = expression.trim_start();
+
+  while !rest.is_empty() {
+    let c = rest.chars().next().unwrap();
+
+    if c.is_ascii_alphanumeric() || c == '_' {
+      let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+      let word = &rest[..end];
+
+      tokens.push(if c.is_ascii_digit() {
+        Token::Number(parse_number(word)?)
+      } else {
+        Token::Identifier(word.to_string())
+      });
+      rest = &rest[end..];
+    } else if let Some(operator) = OPERATORS
+      .iter()
+      .find(|operator| rest.starts_with(*operator))
+    {
+      tokens.push(Token::Operator(operator));
+      rest = &rest[operator.len()..];
+    } else {
+      return Err(format!("unexpected character '{}'", c));
+    }
+
+    rest = rest.trim_start();
+  }
+
+  Ok(tokens)
+}
+
+/// Gets the precedence of a binary operator, higher binds tighter.
+fn precedence(operator: &str) -> Option<u8> {
+  match operator {
+    "*" | "/" | "%" => Some(10),
+    "+" | "-" => Some(9),
+    "<<" | ">>" => Some(8),
+    "<" | ">" | "<=" | ">=" => Some(7),
+    "==" | "!=" => Some(6),
+    "&" => Some(5),
+    "^" => Some(4),
+    "|" => Some(3),
+    "&&" => Some(2),
+    "||" => Some(1),
+    _ => None,
+  }
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.position).cloned();
+    self.position += 1;
+    token
+  }
+
+  fn parse_unary(&mut self) -> Result<i64, String> {
+    match self.next() {
+      Some(Token::Number(value)) => Ok(value),
+      // Identifiers left after the macro expansion are not defined
+      Some(Token::Identifier(_)) => Ok(0),
+      Some(Token::Operator("(")) => {
+        let value = self.parse_binary(0)?;
+        match self.next() {
+          Some(Token::Operator(")")) => Ok(value),
+          _ => Err(String::from("expected ')'")),
+        }
+      }
+      Some(Token::Operator("+")) => self.parse_unary(),
+      Some(Token::Operator("-")) => Ok(self.parse_unary()?.wrapping_neg()),
+      Some(Token::Operator("!")) => Ok((self.parse_unary()? == 0) as i64),
+      Some(Token::Operator("~")) => Ok(!self.parse_unary()?),
+      Some(Token::Operator(operator)) => Err(format!("unexpected '{}'", operator)),
+      None => Err(String::from("unexpected end of the expression")),
+    }
+  }
+
+  fn parse_binary(&mut self, min_precedence: u8) -> Result<i64, String> {
+    let mut left = self.parse_unary()?;
+
+    while let Some(Token::Operator(operator)) = self.peek() {
+      let operator = *operator;
+      let precedence = match precedence(operator) {
+        Some(precedence) if precedence > min_precedence => precedence,
+        _ => break,
+      };
+
+      self.position += 1;
+      let right = self.parse_binary(precedence)?;
+
+      left = match operator {
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err(String::from("division by zero")),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        ">" => (left > right) as i64,
+        "<=" => (left <= right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        "&&" => (left != 0 && right != 0) as i64,
+        "||" => (left != 0 || right != 0) as i64,
+        _ => unreachable!(),
+      };
+    }
+
+    Ok(left)
+  }
+}
+
+/// Evaluates the condition of an `#if` or `#elif` directive, once `defined` and the macros have
+/// been replaced. Identifiers that are left are not defined and evaluate to 0.
+pub fn evaluate(expression: &str) -> Result<i64, String> {
+  let mut parser = Parser {
+    tokens: tokenize(expression)?,
+    position: 0,
+  };
+
+  if parser.tokens.is_empty() {
+    return Err(String::from("empty expression"));
+  }
+
+  let value = parser.parse_binary(0)?;
+
+  match parser.peek() {
+    None => Ok(value),
+    Some(Token::Number(value)) => Err(format!("unexpected '{}'", value)),
+    Some(Token::Identifier(identifier)) => Err(format!("unexpected '{}'", identifier)),
+    Some(Token::Operator(operator)) => Err(format!("unexpected '{}'", operator)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::evaluate;
+
+  #[test]
+  fn follows_the_precedence_of_c() {
+    assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+    assert_eq!(evaluate("(1 + 2) * 3"), Ok(9));
+    assert_eq!(evaluate("1 << 2 + 1"), Ok(8));
+    assert_eq!(evaluate("1 | 2 ^ 3 & 1"), Ok(3));
+    assert_eq!(evaluate("1 < 2 == 1"), Ok(1));
+    assert_eq!(evaluate("0 || 1 && 0"), Ok(0));
+  }
+
+  #[test]
+  fn binary_operators_are_left_associative() {
+    assert_eq!(evaluate("8 - 4 - 2"), Ok(2));
+    assert_eq!(evaluate("16 / 4 / 2"), Ok(2));
+  }
+
+  #[test]
+  fn evaluates_unary_operators() {
+    assert_eq!(evaluate("-2 * 3"), Ok(-6));
+    assert_eq!(evaluate("!0 + !5"), Ok(1));
+    assert_eq!(evaluate("~0"), Ok(-1));
+    assert_eq!(evaluate("0x10 + 010"), Ok(24));
+  }
+
+  #[test]
+  fn undefined_identifiers_are_zero() {
+    assert_eq!(evaluate("MISSING + 1"), Ok(1));
+  }
+
+  #[test]
+  fn rejects_invalid_expressions() {
+    assert!(evaluate("").is_err());
+    assert!(evaluate("1 +").is_err());
+    assert!(evaluate("(1 + 2").is_err());
+    assert!(evaluate("1 2").is_err());
+    assert!(evaluate("1 / 0").is_err());
+  }
+}
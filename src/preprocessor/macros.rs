@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+/// A macro defined with `#define`. Function-like macros have a list of parameters, which can be
+/// empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Macro {
+  pub parameters: Option<Vec<String>>,
+  pub body: String,
+}
+
+fn is_identifier_start(c: char) -> bool {
+  c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Gets the end of the word starting at `start`.
+fn word_end(chars: &[char], start: usize) -> usize {
+  let mut end = start;
+  while end < chars.len() && is_identifier_char(chars[end]) {
+    end += 1;
+  }
+  end
+}
+
+/// Parses the arguments of a function-like macro call, starting after its name. Returns the
+/// arguments and the index after the closing parenthesis, or `None` if the name is not followed
+/// by a complete list of arguments on the same line.
+fn parse_arguments(chars: &[char], start: usize) -> Option<(Vec<String>, usize)> {
+  let mut i = start;
+  while i < chars.len() && chars[i].is_whitespace() {
+    i += 1;
+  }
+
+  if chars.get(i) != Some(&'(') {
+    return None;
+  }
+
+  let mut arguments = Vec::new();
+  let mut argument = String::new();
+  let mut depth = 0;
+
+  for (j, c) in chars.iter().enumerate().skip(i + 1) {
+    match c {
+      '(' => depth += 1,
+      ')' if depth == 0 => {
+        arguments.push(argument.trim().to_string());
+        return Some((arguments, j + 1));
+      }
+      ')' => depth -= 1,
+      ',' if depth == 0 => {
+        arguments.push(argument.trim().to_string());
+        argument.clear();
+        continue;
+      }
+      _ => {}
+    }
+    argument.push(*c);
+  }
+
+  None
+}
+
+/// Replaces the parameters of a function-like macro in its body and applies the `##` operator.
+fn substitute(body: &str, parameters: &[String], arguments: &[String]) -> String {
+  let chars = body.chars().collect::<Vec<char>>();
+  let mut output = String::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    if is_identifier_char(chars[i]) {
+      let end = word_end(&chars, i);
+      let word = chars[i..end].iter().collect::<String>();
+
+      match parameters.iter().position(|parameter| *parameter == word) {
+        Some(index) if is_identifier_start(chars[i]) => output.push_str(&arguments[index]),
+        _ => output.push_str(&word),
+      }
+
+      i = end;
+    } else {
+      output.push(chars[i]);
+      i += 1;
+    }
+  }
+
+  // Token pasting: `a ## b` becomes `ab`
+  while let Some(index) = output.find("##") {
+    let before = output[..index].trim_end().len();
+    let after = index + 2 + (output[index + 2..].len() - output[index + 2..].trim_start().len());
+    output.replace_range(before..after, "");
+  }
+
+  output
+}
+
+fn expand_with(text: &str, macros: &BTreeMap<String, Macro>, disabled: &mut Vec<String>) -> String {
+  let chars = text.chars().collect::<Vec<char>>();
+  let mut output = String::new();
+  let mut i = 0;
+
+  while i < chars.len() {
+    if !is_identifier_char(chars[i]) {
+      output.push(chars[i]);
+      i += 1;
+      continue;
+    }
+
+    let end = word_end(&chars, i);
+    let word = chars[i..end].iter().collect::<String>();
+
+    // Numbers, e.g. the `e5` in `1e5`, are not macros
+    let definition = match macros.get(&word) {
+      Some(definition) if is_identifier_start(chars[i]) && !disabled.contains(&word) => definition,
+      _ => {
+        output.push_str(&word);
+        i = end;
+        continue;
+      }
+    };
+
+    let body = match &definition.parameters {
+      None => {
+        i = end;
+        definition.body.clone()
+      }
+      Some(parameters) => match parse_arguments(&chars, end) {
+        // `F()` is a call without arguments, not with an empty one
+        Some((arguments, after))
+          if arguments.len() == parameters.len()
+            || (parameters.is_empty() && arguments == [""]) =>
+        {
+          let arguments = arguments
+            .iter()
+            .map(|argument| expand_with(argument, macros, disabled))
+            .collect::<Vec<String>>();
+
+          i = after;
+          substitute(&definition.body, parameters, &arguments)
+        }
+        _ => {
+          output.push_str(&word);
+          i = end;
+          continue;
+        }
+      },
+    };
+
+    // A macro is not expanded again inside of its own expansion
+    disabled.push(word);
+    output.push_str(&expand_with(&body, macros, disabled));
+    disabled.pop();
+  }
+
+  output
+}
+
+/// Expands the macros used in a line. Calls of function-like macros must be on a single line,
+/// otherwise they are left as they are.
+pub fn expand(text: &str, macros: &BTreeMap<String, Macro>) -> String {
+  expand_with(text, macros, &mut Vec::new())
+}
+
+/// Parses the part of a `#define` directive after `define`.
+pub fn parse_define(definition: &str) -> Option<(String, Macro)> {
+  let chars = definition.trim().chars().collect::<Vec<char>>();
+
+  if !chars.first().is_some_and(|c| is_identifier_start(*c)) {
+    return None;
+  }
+
+  let end = word_end(&chars, 0);
+  let name = chars[..end].iter().collect::<String>();
+
+  // Only a parenthesis right after the name starts a list of parameters
+  if chars.get(end) == Some(&'(') {
+    let close = end + chars[end..].iter().position(|c| *c == ')')?;
+    let parameters = chars[end + 1..close]
+      .iter()
+      .collect::<String>()
+      .split(',')
+      .map(|parameter| parameter.trim().to_string())
+      .filter(|parameter| !parameter.is_empty())
+      .collect::<Vec<String>>();
+
+    let body = chars[close + 1..].iter().collect::<String>();
+    return Some((
+      name,
+      Macro {
+        parameters: Some(parameters),
+        body: body.trim().to_string(),
+      },
+    ));
+  }
+
+  let body = chars[end..].iter().collect::<String>();
+  Some((
+    name,
+    Macro {
+      parameters: None,
+      body: body.trim().to_string(),
+    },
+  ))
+}
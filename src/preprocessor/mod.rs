@@ -0,0 +1,4 @@
+pub mod expression;
+pub mod macros;
+pub mod preprocess;
+pub mod variants;
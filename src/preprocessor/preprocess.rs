@@ -0,0 +1,432 @@
+use super::expression::evaluate;
+use super::macros::{expand, parse_define, Macro};
+use crate::config::Target;
+use crate::utils::strip_comments::strip_comments;
+use core::fmt;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Errors of the preprocessor. `line` is the directive that caused the error.
+#[derive(Debug, Clone)]
+pub enum PreprocessorError {
+  UnexpectedDirective { line: String },
+  UnterminatedConditional { line: String },
+  InvalidDefine { line: String },
+  InvalidCondition { line: String, message: String },
+  ErrorDirective { message: String },
+}
+
+impl Display for PreprocessorError {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match self {
+      PreprocessorError::UnexpectedDirective { line } => write!(f, "unexpected \"{}\"", line),
+      PreprocessorError::UnterminatedConditional { line } => {
+        write!(f, "\"{}\" has no #endif", line)
+      }
+      PreprocessorError::InvalidDefine { line } => write!(f, "invalid \"{}\"", line),
+      PreprocessorError::InvalidCondition { line, message } => {
+        write!(f, "invalid condition in \"{}\": {}", line, message)
+      }
+      PreprocessorError::ErrorDirective { message } => write!(f, "#error {}", message),
+    }
+  }
+}
+
+/// A `#if`, `#ifdef` or `#ifndef` and its branches.
+struct Conditional {
+  line: String,
+  /// Whether the lines of the current branch are kept.
+  active: bool,
+  /// Whether a previous branch was kept, so the next ones are not.
+  taken: bool,
+  /// Whether the conditional itself is inside of a kept branch.
+  parent_active: bool,
+  has_else: bool,
+}
+
+/// Splits a preprocessor line into its directive and the rest, e.g. `# ifdef FOO` into
+/// `("ifdef", "FOO")`.
+pub(crate) fn parse_directive(line: &str) -> Option<(&str, &str)> {
+  let line = line.trim().strip_prefix('#')?.trim_start();
+  let end = line
+    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+    .unwrap_or(line.len());
+
+  Some((&line[..end], line[end..].trim()))
+}
+
+/// Joins the lines ending with a backslash with the next one.
+fn logical_lines(source: &str) -> Vec<String> {
+  let mut lines = Vec::new();
+  let mut current: Option<String> = None;
+
+  for line in source.lines() {
+    let mut text = current.take().unwrap_or_default();
+
+    match line.strip_suffix('\\') {
+      Some(line) => {
+        text.push_str(line);
+        current = Some(text);
+      }
+      None => {
+        text.push_str(line);
+        lines.push(text);
+      }
+    }
+  }
+
+  lines.extend(current);
+  lines
+}
+
+/// Blanks the conditional directives found inside of braces, which the glsl parser does not
+/// accept. The lines of every branch are kept, so the result is only meant to find the
+/// declarations of a file before it is preprocessed.
+pub fn blank_nested_conditionals(source: &str) -> String {
+  let mut depth = 0;
+  let mut lines = Vec::new();
+
+  for line in source.lines() {
+    let is_conditional = matches!(
+      parse_directive(line),
+      Some(("if" | "ifdef" | "ifndef" | "elif" | "else" | "endif", _))
+    );
+
+    if depth > 0 && is_conditional {
+      lines.push("");
+      continue;
+    }
+
+    for c in line.chars() {
+      match c {
+        '{' => depth += 1,
+        '}' => depth -= 1,
+        _ => {}
+      }
+    }
+
+    lines.push(line);
+  }
+
+  lines.join("\n")
+}
+
+/// Replaces `defined X` and `defined(X)` in a condition with 1 or 0.
+fn replace_defined(condition: &str, macros: &BTreeMap<String, Macro>) -> Result<String, String> {
+  let mut output = String::new();
+  let mut rest = condition;
+
+  while let Some(index) = rest.find("defined") {
+    let before = &rest[..index];
+    let after = &rest[index + "defined".len()..];
+
+    // Only the whole word, not e.g. `IS_defined`
+    let is_word = !before.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+      && !after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+
+    output.push_str(before);
+
+    if !is_word {
+      output.push_str("defined");
+      rest = after;
+      continue;
+    }
+
+    let operand = after.trim_start();
+    let (name, rest_after) = match operand.strip_prefix('(') {
+      Some(operand) => {
+        let (name, rest_after) = operand
+          .split_once(')')
+          .ok_or_else(|| String::from("expected ')' after defined"))?;
+        (name.trim(), rest_after)
+      }
+      None => {
+        let end = operand
+          .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+          .unwrap_or(operand.len());
+        (&operand[..end], &operand[end..])
+      }
+    };
+
+    if name.is_empty() {
+      return Err(String::from("expected a macro name after defined"));
+    }
+
+    output.push_str(if macros.contains_key(name) {
+      " 1 "
+    } else {
+      " 0 "
+    });
+    rest = rest_after;
+  }
+
+  output.push_str(rest);
+  Ok(output)
+}
+
+fn evaluate_condition(
+  condition: &str,
+  macros: &BTreeMap<String, Macro>,
+  line: &str,
+) -> Result<bool, PreprocessorError> {
+  let invalid = |message| PreprocessorError::InvalidCondition {
+    line: line.to_string(),
+    message,
+  };
+
+  let condition = replace_defined(condition, macros).map_err(invalid)?;
+  let value = evaluate(&expand(&condition, macros)).map_err(invalid)?;
+
+  Ok(value != 0)
+}
+
+/// Gets the macros that the compiler defines: `__VERSION__`, and `GL_ES` for GLSL ES shaders.
+/// Shaders without a `#version` are GLSL ES 1.00 in WebGL and GLSL 1.10 in OpenGL.
+fn builtin_macros(lines: &[String], target: Target) -> BTreeMap<String, Macro> {
+  let version = lines.iter().find_map(|line| match parse_directive(line) {
+    Some(("version", version)) => Some(version.split_whitespace().collect::<Vec<&str>>()),
+    _ => None,
+  });
+
+  let (version, is_es) = match version.as_deref() {
+    Some([number, profile, ..]) => (number.to_string(), *profile == "es" || *number == "100"),
+    Some([number]) => (number.to_string(), *number == "100"),
+    _ => match target {
+      Target::WebGL1 | Target::WebGL2 => (String::from("100"), true),
+      Target::OpenGL => (String::from("110"), false),
+    },
+  };
+
+  let mut macros = BTreeMap::new();
+  macros.insert(
+    String::from("__VERSION__"),
+    Macro {
+      parameters: None,
+      body: version,
+    },
+  );
+
+  if is_es {
+    macros.insert(
+      String::from("GL_ES"),
+      Macro {
+        parameters: None,
+        body: String::from("1"),
+      },
+    );
+  }
+
+  macros
+}
+
+/// Runs the preprocessor on a combined shader: conditionals are evaluated, the lines of the
+/// branches that are not taken are removed and the macros are expanded. Defines from the config
+/// file and the command line are already inserted in the source as `#define` directives.
+///
+/// `#define`, `#undef`, `#version`, `#extension` and `#pragma` directives are kept, so the
+/// output can still be compiled on its own. Comments are removed.
+pub fn preprocess(source: &str, target: Target) -> Result<String, PreprocessorError> {
+  let lines = logical_lines(&strip_comments(source));
+  let mut macros = builtin_macros(&lines, target);
+
+  let mut conditionals: Vec<Conditional> = Vec::new();
+  let mut output = Vec::new();
+
+  for line in lines {
+    let active = conditionals
+      .last()
+      .is_none_or(|conditional| conditional.active);
+
+    let (directive, rest) = match parse_directive(&line) {
+      Some(directive) => directive,
+      None => {
+        if active {
+          output.push(expand(&line, &macros));
+        }
+        continue;
+      }
+    };
+
+    let line = line.trim();
+
+    let unexpected = || PreprocessorError::UnexpectedDirective {
+      line: line.to_string(),
+    };
+
+    match directive {
+      "ifdef" | "ifndef" | "if" => {
+        // The conditions of branches that are not taken are not evaluated
+        let value = active
+          && match directive {
+            "ifdef" => macros.contains_key(rest),
+            "ifndef" => !macros.contains_key(rest),
+            _ => evaluate_condition(rest, &macros, line)?,
+          };
+
+        conditionals.push(Conditional {
+          line: line.to_string(),
+          active: value,
+          taken: value,
+          parent_active: active,
+          has_else: false,
+        });
+      }
+      "elif" => {
+        let conditional = conditionals.last_mut().ok_or_else(unexpected)?;
+        if conditional.has_else {
+          return Err(unexpected());
+        }
+
+        let value = conditional.parent_active
+          && !conditional.taken
+          && evaluate_condition(rest, &macros, line)?;
+
+        conditional.active = value;
+        conditional.taken |= value;
+      }
+      "else" => {
+        let conditional = conditionals.last_mut().ok_or_else(unexpected)?;
+        if conditional.has_else {
+          return Err(unexpected());
+        }
+
+        conditional.active = conditional.parent_active && !conditional.taken;
+        conditional.taken = true;
+        conditional.has_else = true;
+      }
+      "endif" => {
+        conditionals.pop().ok_or_else(unexpected)?;
+      }
+      _ if !active => {}
+      "define" => {
+        let (name, definition) = parse_define(rest).ok_or(PreprocessorError::InvalidDefine {
+          line: line.to_string(),
+        })?;
+        macros.insert(name, definition);
+        output.push(line.to_string());
+      }
+      "undef" => {
+        macros.remove(rest);
+        output.push(line.to_string());
+      }
+      "error" => {
+        return Err(PreprocessorError::ErrorDirective {
+          message: rest.to_string(),
+        });
+      }
+      // The null directive
+      "" => {}
+      _ => output.push(line.to_string()),
+    }
+  }
+
+  if let Some(conditional) = conditionals.pop() {
+    return Err(PreprocessorError::UnterminatedConditional {
+      line: conditional.line,
+    });
+  }
+
+  let mut output = output.join("\n");
+  output.push('\n');
+  Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{preprocess, PreprocessorError};
+  use crate::config::Target;
+
+  /// Gets the lines of the output that are not directives nor empty.
+  fn kept_lines(source: &str) -> Vec<String> {
+    preprocess(source, Target::WebGL2)
+      .unwrap()
+      .lines()
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(String::from)
+      .collect()
+  }
+
+  #[test]
+  fn evaluates_defined() {
+    let source = "#define A\n#if defined(A) && !defined B\na\n#endif\n#if defined B || defined(C)\nb\n#endif\n";
+
+    assert_eq!(kept_lines(source), ["a"]);
+  }
+
+  #[test]
+  fn defined_only_matches_whole_words() {
+    let source = "#define IS_defined 1\n#if IS_defined\na\n#endif\n";
+
+    assert_eq!(kept_lines(source), ["a"]);
+  }
+
+  #[test]
+  fn expands_macros_in_conditions() {
+    let source = "#define SIZE (2 * 4)\n#if SIZE == 8 && SIZE + 1 > 8\na\n#else\nb\n#endif\n";
+
+    assert_eq!(kept_lines(source), ["a"]);
+  }
+
+  #[test]
+  fn takes_the_first_matching_branch() {
+    let source = "#define LEVEL 2\n#if LEVEL == 1\na\n#elif LEVEL == 2\nb\n#elif LEVEL >= 2\nc\n#else\nd\n#endif\n";
+
+    assert_eq!(kept_lines(source), ["b"]);
+  }
+
+  #[test]
+  fn nested_conditionals_of_a_skipped_branch_are_skipped() {
+    let source =
+      "#if 0\n#ifndef MISSING\na\n#else\nb\n#endif\n#elif 1\n#ifdef GL_ES\nc\n#endif\n#endif\n";
+
+    assert_eq!(kept_lines(source), ["c"]);
+  }
+
+  #[test]
+  fn conditions_of_skipped_branches_are_not_evaluated() {
+    let source = "#if 0\n#if 1 / 0\na\n#endif\n#endif\nb\n";
+
+    assert_eq!(kept_lines(source), ["b"]);
+  }
+
+  #[test]
+  fn defines_of_skipped_branches_are_ignored() {
+    let source = "#ifdef MISSING\n#define A\n#endif\n#ifdef A\na\n#endif\n";
+
+    assert!(kept_lines(source).is_empty());
+  }
+
+  #[test]
+  fn sets_the_builtin_macros() {
+    let source = "#version 300 es\n#if __VERSION__ == 300 && defined GL_ES\na\n#endif\n";
+    assert_eq!(kept_lines(source), ["a"]);
+
+    let source = "#version 330\n#ifdef GL_ES\na\n#endif\n";
+    assert!(kept_lines(source).is_empty());
+  }
+
+  #[test]
+  fn reports_unbalanced_conditionals() {
+    assert!(matches!(
+      preprocess("#ifdef A\na\n", Target::WebGL2),
+      Err(PreprocessorError::UnterminatedConditional { .. })
+    ));
+    assert!(matches!(
+      preprocess("#endif\n", Target::WebGL2),
+      Err(PreprocessorError::UnexpectedDirective { .. })
+    ));
+    assert!(matches!(
+      preprocess("#if 1\n#else\n#else\n#endif\n", Target::WebGL2),
+      Err(PreprocessorError::UnexpectedDirective { .. })
+    ));
+  }
+
+  #[test]
+  fn reports_error_directives_of_taken_branches() {
+    assert!(preprocess("#if 0\n#error unsupported\n#endif\n", Target::WebGL2).is_ok());
+    assert!(matches!(
+      preprocess("#if 1\n#error unsupported\n#endif\n", Target::WebGL2),
+      Err(PreprocessorError::ErrorDirective { .. })
+    ));
+  }
+}
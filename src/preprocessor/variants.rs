@@ -0,0 +1,151 @@
+use crate::config::DefineValue;
+use std::collections::BTreeMap;
+
+/// A define that changes between the variants of a shader, and the values it takes. The first
+/// value is the default one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantOption {
+  pub name: String,
+  pub values: Vec<DefineValue>,
+}
+
+/// The most variants a shader can have. Every variant is preprocessed and embedded on its own,
+/// so the count grows exponentially with the options.
+pub const MAX_VARIANTS: usize = 64;
+
+/// The values of the variant options for one variant, in the order of the options.
+pub type Variant = Vec<(String, DefineValue)>;
+
+/// Gets the options declared with `#pragma variant NAME` (defined or not) and
+/// `#pragma variant NAME value1 value2 ...`.
+pub fn declared_variant_options(source: &str) -> Vec<VariantOption> {
+  source
+    .lines()
+    .filter_map(|line| {
+      let directive = line.trim().strip_prefix('#')?.trim_start();
+      let mut words = directive.strip_prefix("pragma")?.split_whitespace();
+
+      if words.next()? != "variant" {
+        return None;
+      }
+
+      let name = words.next()?.to_string();
+      let values = match words.map(DefineValue::parse).collect::<Vec<DefineValue>>() {
+        values if values.is_empty() => vec![DefineValue::Bool(false), DefineValue::Bool(true)],
+        values => values,
+      };
+
+      Some(VariantOption { name, values })
+    })
+    .collect()
+}
+
+/// Combines the options of the config file with the ones declared in the shader. The config
+/// file wins when both declare the same define.
+pub fn variant_options(
+  configured: &BTreeMap<String, Vec<DefineValue>>,
+  source: &str,
+) -> Vec<VariantOption> {
+  let mut options = configured
+    .iter()
+    .filter(|(_, values)| !values.is_empty())
+    .map(|(name, values)| VariantOption {
+      name: name.clone(),
+      values: values.clone(),
+    })
+    .collect::<Vec<VariantOption>>();
+
+  for option in declared_variant_options(source) {
+    if !options.iter().any(|other| other.name == option.name) {
+      options.push(option);
+    }
+  }
+
+  options
+}
+
+/// Gets every combination of the values of the options. The first variant uses the first value
+/// of every option, and the last options change first. More than `MAX_VARIANTS` combinations are
+/// an error.
+pub fn permutations(options: &[VariantOption]) -> Result<Vec<Variant>, String> {
+  let count = options.iter().fold(1usize, |count, option| {
+    count.saturating_mul(option.values.len())
+  });
+
+  if count > MAX_VARIANTS {
+    return Err(format!(
+      "its {} variant options have {} combinations, more than the {} allowed",
+      options.len(),
+      count,
+      MAX_VARIANTS
+    ));
+  }
+
+  let mut variants = vec![Vec::new()];
+
+  for option in options {
+    variants = variants
+      .into_iter()
+      .flat_map(|variant| {
+        option.values.iter().map(move |value| {
+          let mut variant = variant.clone();
+          variant.push((option.name.clone(), value.clone()));
+          variant
+        })
+      })
+      .collect();
+  }
+
+  Ok(variants)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{permutations, VariantOption, MAX_VARIANTS};
+  use crate::config::DefineValue;
+
+  fn option(name: &str, count: usize) -> VariantOption {
+    VariantOption {
+      name: name.to_string(),
+      values: (0..count as i64).map(DefineValue::Integer).collect(),
+    }
+  }
+
+  #[test]
+  fn the_last_options_change_first() {
+    let variants = permutations(&[option("A", 2), option("B", 2)]).unwrap();
+    let values = variants
+      .iter()
+      .map(|variant| {
+        variant
+          .iter()
+          .map(|(_, value)| value.clone())
+          .collect::<Vec<DefineValue>>()
+      })
+      .collect::<Vec<Vec<DefineValue>>>();
+
+    use DefineValue::Integer;
+    assert_eq!(
+      values,
+      [
+        [Integer(0), Integer(0)],
+        [Integer(0), Integer(1)],
+        [Integer(1), Integer(0)],
+        [Integer(1), Integer(1)]
+      ]
+    );
+  }
+
+  #[test]
+  fn caps_the_number_of_variants() {
+    assert_eq!(
+      permutations(&[option("A", MAX_VARIANTS)]).unwrap().len(),
+      MAX_VARIANTS
+    );
+
+    let options = (0..64)
+      .map(|i| option(&format!("OPTION_{}", i), 2))
+      .collect::<Vec<VariantOption>>();
+    assert!(permutations(&options).is_err());
+  }
+}
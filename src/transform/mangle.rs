@@ -0,0 +1,208 @@
+use super::minify::Renamer;
+use super::tree_shake::has_storage;
+use crate::utils::strip_comments::strip_comments;
+use crate::utils::symbols::{declared_symbols, referenced_symbols};
+use crate::utils::transpile::show_external_declaration;
+use glsl::parser::Parse as _;
+use glsl::syntax::{
+  Declaration, ExternalDeclaration, Preprocessor, PreprocessorDefine, ShaderStage, StorageQualifier,
+};
+use glsl::visitor::HostMut;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Number of base 36 digits of a mangled name.
+const MANGLED_NAME_LENGTH: usize = 5;
+
+/// Gets the mangled name of a variable. It only depends on the original name, so that a varying
+/// gets the same name in the vertex and in the fragment shader.
+fn mangled_name(name: &str) -> String {
+  // FNV-1a
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for byte in name.bytes() {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+
+  let mut mangled = String::from("_");
+  for _ in 0..MANGLED_NAME_LENGTH {
+    mangled.push(char::from_digit((hash % 36) as u32, 36).unwrap());
+    hash /= 36;
+  }
+
+  mangled
+}
+
+/// Gets the uniforms, inputs and outputs declared by a top-level declaration. Blocks are not
+/// included, as their fields are matched by name.
+fn interface_names(declaration: &ExternalDeclaration) -> Vec<String> {
+  let is_interface = |storage: &StorageQualifier| {
+    matches!(
+      storage,
+      StorageQualifier::Uniform
+        | StorageQualifier::In
+        | StorageQualifier::Out
+        | StorageQualifier::Attribute
+        | StorageQualifier::Varying
+    )
+  };
+
+  match declaration {
+    ExternalDeclaration::Declaration(Declaration::InitDeclaratorList(list))
+      if has_storage(&list.head.ty.qualifier, is_interface) =>
+    {
+      let mut names = Vec::new();
+      names.extend(list.head.name.iter().map(|name| name.to_string()));
+      names.extend(list.tail.iter().map(|tail| tail.ident.ident.to_string()));
+      names
+        .into_iter()
+        .filter(|name| !name.starts_with("gl_"))
+        .collect()
+    }
+    _ => Vec::new(),
+  }
+}
+
+/// Renames the whole words of a macro body found in `names`.
+fn rename_words(source: &str, names: &HashMap<String, String>) -> String {
+  let mut output = String::new();
+  let mut word = String::new();
+
+  for c in source.chars().chain(std::iter::once(' ')) {
+    if c.is_ascii_alphanumeric() || c == '_' {
+      word.push(c);
+      continue;
+    }
+
+    output.push_str(names.get(&word).unwrap_or(&word));
+    word.clear();
+    output.push(c);
+  }
+
+  output.pop();
+  output
+}
+
+/// Renames the uniforms, inputs and outputs of a shader to hashed names, and returns the new
+/// source together with the map from the original names to the new ones.
+///
+/// The output is re-emitted from the AST, so comments and formatting are not preserved.
+pub fn mangle(source: &str) -> Result<(String, BTreeMap<String, String>), String> {
+  let mut ast = ShaderStage::parse(strip_comments(source)).map_err(|e| e.to_string())?;
+
+  let mut taken = BTreeSet::new();
+  let mut interface = BTreeSet::new();
+  for declaration in &ast.0 .0 {
+    taken.extend(declared_symbols(declaration));
+    taken.extend(referenced_symbols(declaration));
+    interface.extend(interface_names(declaration));
+  }
+
+  let mut names = HashMap::new();
+  let mut used_names = BTreeMap::new();
+  for name in interface {
+    let mangled = mangled_name(&name);
+
+    if taken.contains(&mangled) {
+      return Err(format!(
+        "the mangled name of \"{}\" ({}) is already used by the shader",
+        name, mangled
+      ));
+    }
+
+    if let Some(other) = used_names.insert(mangled.clone(), name.clone()) {
+      return Err(format!(
+        "\"{}\" and \"{}\" have the same mangled name ({})",
+        other, name, mangled
+      ));
+    }
+
+    names.insert(name, mangled);
+  }
+
+  ast.visit_mut(&mut Renamer { names: &names });
+
+  // Macros are not parsed, so the names they use are renamed as words
+  for declaration in ast.0 .0.iter_mut() {
+    if let ExternalDeclaration::Preprocessor(Preprocessor::Define(define)) = declaration {
+      let value = match define {
+        PreprocessorDefine::ObjectLike { value, .. } => value,
+        PreprocessorDefine::FunctionLike { value, .. } => value,
+      };
+
+      *value = rename_words(value, &names);
+    }
+  }
+
+  let mut output = String::new();
+  for declaration in &ast.0 .0 {
+    show_external_declaration(&mut output, declaration);
+  }
+
+  Ok((output, names.into_iter().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{mangle, mangled_name};
+
+  #[test]
+  fn renames_the_interface() {
+    let (source, names) = mangle(
+      "#version 300 es
+precision highp float;
+uniform vec4 uColor;
+out vec4 color;
+#define TINT (uColor * 2.0)
+void main() { color = TINT; }
+",
+    )
+    .unwrap();
+
+    assert_eq!(names["uColor"], mangled_name("uColor"));
+    assert_eq!(names["color"], mangled_name("color"));
+    assert!(!source.contains("uColor"));
+    assert!(source.contains(&format!("({} * 2.0)", mangled_name("uColor"))));
+  }
+
+  #[test]
+  fn mangled_names_only_depend_on_the_name() {
+    assert_eq!(mangled_name("vNormal"), mangled_name("vNormal"));
+    assert_ne!(mangled_name("vNormal"), mangled_name("vColor"));
+    assert_eq!(mangled_name("vNormal").len(), 6);
+  }
+
+  #[test]
+  fn keeps_the_builtins_and_the_locals() {
+    let (source, names) = mangle(
+      "#version 300 es
+precision highp float;
+uniform float uScale;
+void main() { float scale = uScale; gl_PointSize = scale; }
+",
+    )
+    .unwrap();
+
+    assert_eq!(names.len(), 1);
+    assert!(source.contains("gl_PointSize"));
+    assert!(source.contains("scale"));
+  }
+
+  #[test]
+  fn rejects_names_already_used_by_the_shader() {
+    let taken = mangled_name("uColor");
+    let source = format!(
+      "#version 300 es
+precision highp float;
+uniform vec4 uColor;
+out vec4 color;
+float {}(float x) {{ return x; }}
+void main() {{ color = uColor; }}
+",
+      taken
+    );
+
+    let error = mangle(&source).unwrap_err();
+    assert!(error.contains("uColor"), "{}", error);
+    assert!(error.contains(&taken), "{}", error);
+  }
+}
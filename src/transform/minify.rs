@@ -0,0 +1,347 @@
+use crate::utils::strip_comments::strip_comments;
+use crate::utils::symbols::{declared_symbols, referenced_symbols};
+use crate::utils::transpile::show_external_declaration;
+use glsl::parser::Parse as _;
+use glsl::syntax::{
+  Condition, Expr, ExternalDeclaration, FunIdentifier, FunctionParameterDeclarator,
+  FunctionPrototype, Identifier, ShaderStage, SingleDeclaration, SingleDeclarationNoType,
+  TranslationUnit,
+};
+use glsl::visitor::{Host, HostMut, Visit, Visitor, VisitorMut};
+use std::collections::{BTreeSet, HashMap};
+
+/// Keywords and reserved words short enough to be generated as names.
+const RESERVED_WORDS: [&str; 16] = [
+  "do", "if", "in", "for", "int", "out", "asm", "bool", "case", "else", "enum", "flat", "goto",
+  "long", "uint", "void",
+];
+
+/// Generates the names `a`, `b`, ..., `Z`, `aa`, `ab`, ... skipping the ones already in use.
+#[derive(Clone)]
+struct NameGenerator<'a> {
+  index: usize,
+  taken: &'a BTreeSet<String>,
+}
+
+impl NameGenerator<'_> {
+  fn next_name(&mut self) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+    loop {
+      let mut index = self.index;
+      let mut name = String::new();
+
+      loop {
+        name.insert(0, ALPHABET[index % ALPHABET.len()] as char);
+        index /= ALPHABET.len();
+        if index == 0 {
+          break;
+        }
+        index -= 1;
+      }
+
+      self.index += 1;
+
+      if !self.taken.contains(&name) && !RESERVED_WORDS.contains(&name.as_str()) {
+        return name;
+      }
+    }
+  }
+}
+
+/// Collects the names declared inside of a function: its parameters and local variables.
+struct LocalsVisitor {
+  locals: Vec<String>,
+}
+
+impl LocalsVisitor {
+  fn push(&mut self, identifier: &Identifier) {
+    if !self.locals.contains(&identifier.0) {
+      self.locals.push(identifier.0.clone());
+    }
+  }
+}
+
+impl Visitor for LocalsVisitor {
+  fn visit_single_declaration(&mut self, declaration: &SingleDeclaration) -> Visit {
+    if let Some(name) = &declaration.name {
+      self.push(name);
+    }
+    Visit::Children
+  }
+
+  fn visit_single_declaration_no_type(&mut self, declaration: &SingleDeclarationNoType) -> Visit {
+    self.push(&declaration.ident.ident);
+    Visit::Children
+  }
+
+  fn visit_function_parameter_declarator(
+    &mut self,
+    declarator: &FunctionParameterDeclarator,
+  ) -> Visit {
+    self.push(&declarator.ident.ident);
+    Visit::Children
+  }
+
+  fn visit_condition(&mut self, condition: &Condition) -> Visit {
+    if let Condition::Assignment(_, name, _) = condition {
+      self.push(name);
+    }
+    Visit::Children
+  }
+}
+
+/// Renames the variables, functions and declarations found in `names`. Fields and struct
+/// names are left untouched.
+pub struct Renamer<'a> {
+  pub names: &'a HashMap<String, String>,
+}
+
+impl Renamer<'_> {
+  fn rename(&self, identifier: &mut Identifier) {
+    if let Some(name) = self.names.get(&identifier.0) {
+      identifier.0 = name.clone();
+    }
+  }
+}
+
+impl VisitorMut for Renamer<'_> {
+  fn visit_expr(&mut self, expr: &mut Expr) -> Visit {
+    match expr {
+      Expr::Variable(identifier) => self.rename(identifier),
+      Expr::FunCall(FunIdentifier::Identifier(identifier), _) => self.rename(identifier),
+      _ => {}
+    }
+    Visit::Children
+  }
+
+  fn visit_single_declaration(&mut self, declaration: &mut SingleDeclaration) -> Visit {
+    if let Some(name) = &mut declaration.name {
+      self.rename(name);
+    }
+    Visit::Children
+  }
+
+  fn visit_single_declaration_no_type(
+    &mut self,
+    declaration: &mut SingleDeclarationNoType,
+  ) -> Visit {
+    self.rename(&mut declaration.ident.ident);
+    Visit::Children
+  }
+
+  fn visit_function_parameter_declarator(
+    &mut self,
+    declarator: &mut FunctionParameterDeclarator,
+  ) -> Visit {
+    self.rename(&mut declarator.ident.ident);
+    Visit::Children
+  }
+
+  fn visit_function_prototype(&mut self, prototype: &mut FunctionPrototype) -> Visit {
+    self.rename(&mut prototype.name);
+    Visit::Children
+  }
+
+  fn visit_condition(&mut self, condition: &mut Condition) -> Visit {
+    if let Condition::Assignment(_, name, _) = condition {
+      self.rename(name);
+    }
+    Visit::Children
+  }
+}
+
+/// Shortens the names of the functions (except `main`) and of the local variables. Global
+/// variables, and thus the reflected interface, keep their names. Names used by macros or
+/// preprocessor conditions are kept too, as they are not parsed.
+fn shorten_names(ast: &mut TranslationUnit) {
+  let declarations = &ast.0 .0;
+  let mut taken = BTreeSet::new();
+  let mut globals = BTreeSet::new();
+  let mut pinned = BTreeSet::new();
+  let mut functions = Vec::new();
+
+  for declaration in declarations {
+    let names = declared_symbols(declaration);
+    let references = referenced_symbols(declaration);
+
+    if let ExternalDeclaration::Preprocessor(_) = declaration {
+      pinned.extend(references.iter().cloned());
+    }
+
+    match declaration {
+      ExternalDeclaration::FunctionDefinition(_)
+      | ExternalDeclaration::Declaration(glsl::syntax::Declaration::FunctionPrototype(_)) => {
+        functions.extend(names.iter().cloned())
+      }
+      _ => {}
+    }
+
+    taken.extend(references);
+    taken.extend(names.iter().cloned());
+    globals.extend(names);
+  }
+
+  let mut generator = NameGenerator {
+    index: 0,
+    taken: &taken,
+  };
+
+  let mut function_names = HashMap::new();
+  for function in functions {
+    if function != "main" && !pinned.contains(&function) && !function_names.contains_key(&function)
+    {
+      function_names.insert(function, generator.next_name());
+    }
+  }
+
+  ast.visit_mut(&mut Renamer {
+    names: &function_names,
+  });
+
+  for declaration in ast.0 .0.iter_mut() {
+    if let ExternalDeclaration::FunctionDefinition(function) = declaration {
+      let mut visitor = LocalsVisitor { locals: Vec::new() };
+      function.visit(&mut visitor);
+
+      // Every function starts again after the names given to functions
+      let mut local_generator = generator.clone();
+      let local_names = visitor
+        .locals
+        .into_iter()
+        .filter(|local| !globals.contains(local) && !pinned.contains(local))
+        .map(|local| (local, local_generator.next_name()))
+        .collect::<HashMap<String, String>>();
+
+      function.statement.visit_mut(&mut Renamer {
+        names: &local_names,
+      });
+
+      for parameter in function.prototype.parameters.iter_mut() {
+        parameter.visit_mut(&mut Renamer {
+          names: &local_names,
+        });
+      }
+    }
+  }
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Removes the whitespace that is not needed to separate tokens. Preprocessor directives stay
+/// on their own lines.
+fn compact_whitespace(source: &str) -> String {
+  let mut output = String::new();
+
+  for line in source.lines() {
+    let line = line.trim();
+
+    if line.starts_with('#') {
+      if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+      }
+      output.push_str(line);
+      output.push('\n');
+      continue;
+    }
+
+    let mut pending_space = !output.is_empty() && !output.ends_with('\n');
+    for c in line.chars() {
+      if c.is_whitespace() {
+        pending_space = true;
+        continue;
+      }
+
+      if pending_space {
+        // `a - -b` must not become `a--b`
+        let previous = output.chars().last().unwrap_or('\n');
+        if (is_word_char(previous) && is_word_char(c)) || (previous == c && "+-".contains(c)) {
+          output.push(' ');
+        }
+        pending_space = false;
+      }
+
+      output.push(c);
+    }
+  }
+
+  output
+}
+
+/// Minifies a shader: comments are removed, functions and local variables get short names and
+/// the whitespace that is not needed is removed. The names of global variables do not change.
+pub fn minify(source: &str) -> Result<String, String> {
+  let mut ast = ShaderStage::parse(strip_comments(source)).map_err(|e| e.to_string())?;
+  shorten_names(&mut ast);
+
+  let mut output = String::new();
+  for declaration in &ast.0 .0 {
+    show_external_declaration(&mut output, declaration);
+  }
+
+  Ok(compact_whitespace(&output))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{compact_whitespace, minify};
+
+  #[test]
+  fn removes_the_whitespace_between_tokens() {
+    assert_eq!(
+      compact_whitespace("float  a = b - -c;\n  return a ;\n"),
+      "float a=b- -c;return a;"
+    );
+    assert_eq!(compact_whitespace("a + +b"), "a+ +b");
+  }
+
+  #[test]
+  fn keeps_directives_on_their_own_lines() {
+    assert_eq!(
+      compact_whitespace("#version 300 es\nfloat a;\n#define B 1\nfloat c;\n"),
+      "#version 300 es\nfloat a;\n#define B 1\nfloat c;"
+    );
+  }
+
+  #[test]
+  fn shortens_the_functions_and_the_locals() {
+    let output = minify(
+      "#version 300 es
+precision highp float;
+uniform float a;
+out vec4 color;
+// Scales a value
+float scaleValue(float value) { return value * a; }
+void main() { float result = scaleValue(1.0); color = vec4(result); }
+",
+    )
+    .unwrap();
+
+    // `a` is taken by the uniform, which keeps its name
+    assert!(output.contains("uniform float a;"), "{}", output);
+    assert!(
+      output.contains("float b(float c){return c*a;}"),
+      "{}",
+      output
+    );
+    assert!(output.contains("void main(){float c=b(1.);"), "{}", output);
+    assert!(!output.contains("Scales"), "{}", output);
+  }
+
+  #[test]
+  fn keeps_the_names_used_by_macros() {
+    let output = minify(
+      "#version 300 es
+precision highp float;
+#define APPLY(x) helper(x)
+float helper(float value) { return value; }
+void main() { gl_FragDepth = APPLY(0.5); }
+",
+    )
+    .unwrap();
+
+    assert!(output.contains("float helper("), "{}", output);
+  }
+}
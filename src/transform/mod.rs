@@ -0,0 +1,3 @@
+pub mod mangle;
+pub mod minify;
+pub mod tree_shake;
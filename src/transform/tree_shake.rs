@@ -0,0 +1,70 @@
+use crate::utils::strip_comments::strip_comments;
+use crate::utils::symbols::reachable_declarations;
+use crate::utils::transpile::show_external_declaration;
+use glsl::parser::Parse as _;
+use glsl::syntax::{
+  Declaration, ExternalDeclaration, ShaderStage, StorageQualifier, TypeQualifier, TypeQualifierSpec,
+};
+
+/// Whether the qualifier has a storage qualifier for which `storage` returns true.
+pub fn has_storage(
+  qualifier: &Option<TypeQualifier>,
+  storage: impl Fn(&StorageQualifier) -> bool,
+) -> bool {
+  qualifier
+    .iter()
+    .flat_map(|qualifier| &qualifier.qualifiers.0)
+    .any(|spec| match spec {
+      TypeQualifierSpec::Storage(qualifier) => storage(qualifier),
+      _ => false,
+    })
+}
+
+/// Whether the declaration is part of the interface of the shader, which has to be kept even if
+/// `main` does not use it.
+fn is_interface(declaration: &ExternalDeclaration, keep_uniforms: bool) -> bool {
+  let is_kept_storage = |storage: &StorageQualifier| match storage {
+    StorageQualifier::Uniform => keep_uniforms,
+    StorageQualifier::In
+    | StorageQualifier::Out
+    | StorageQualifier::InOut
+    | StorageQualifier::Attribute
+    | StorageQualifier::Varying
+    | StorageQualifier::Buffer
+    | StorageQualifier::Shared => true,
+    _ => false,
+  };
+
+  match declaration {
+    ExternalDeclaration::Declaration(Declaration::InitDeclaratorList(list)) => {
+      has_storage(&list.head.ty.qualifier, is_kept_storage)
+    }
+    ExternalDeclaration::Declaration(Declaration::Block(block)) => {
+      has_storage(&Some(block.qualifier.clone()), is_kept_storage)
+    }
+    _ => false,
+  }
+}
+
+/// Removes the functions, prototypes, structs, constants, uniforms and macros that cannot be
+/// reached from `main`. Inputs and outputs are always kept, and so are uniforms with
+/// `keep_uniforms` so that the reflected interface does not change.
+///
+/// The output is re-emitted from the AST, so comments and formatting are not preserved.
+pub fn tree_shake(source: &str, keep_uniforms: bool) -> Result<String, String> {
+  let ast = ShaderStage::parse(strip_comments(source)).map_err(|e| e.to_string())?;
+  let declarations = &ast.0 .0;
+
+  let reachable = reachable_declarations(declarations, &[String::from("main")], |declaration| {
+    is_interface(declaration, keep_uniforms)
+  });
+
+  let mut output = String::new();
+  for (declaration, reachable) in declarations.iter().zip(reachable) {
+    if reachable {
+      show_external_declaration(&mut output, declaration);
+    }
+  }
+
+  Ok(output)
+}
@@ -1,14 +1,27 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
 pub const FRAGMENT_SHADER_EXTENSIONS: [&str; 2] = ["frag", "fs"];
 pub const VERTEX_SHADER_EXTENSIONS: [&str; 2] = ["vert", "vs"];
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ShaderType {
   Fragment,
   Vertex,
 }
 
-pub fn get_shader_type(file_path: &std::path::PathBuf) -> Option<ShaderType> {
-  let extension = file_path.extension().unwrap().to_str().unwrap();
+/// Gets the stage of a shader from its extension. Extensions in `stages` (usually coming from
+/// the config file) take precedence over the built-in ones.
+pub fn get_shader_type(
+  file_path: &std::path::Path,
+  stages: &BTreeMap<String, ShaderType>,
+) -> Option<ShaderType> {
+  let extension = file_path.extension()?.to_str()?;
+
+  if let Some(shader_type) = stages.get(extension) {
+    return Some(*shader_type);
+  }
 
   if FRAGMENT_SHADER_EXTENSIONS.contains(&extension) {
     return Some(ShaderType::Fragment);
@@ -16,5 +29,5 @@ pub fn get_shader_type(file_path: &std::path::PathBuf) -> Option<ShaderType> {
     return Some(ShaderType::Vertex);
   }
 
-  return None;
+  None
 }
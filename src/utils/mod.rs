@@ -1,2 +1,6 @@
 pub mod get_shader_type;
 pub mod log;
+pub mod path;
+pub mod strip_comments;
+pub mod symbols;
+pub mod transpile;
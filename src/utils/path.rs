@@ -0,0 +1,7 @@
+use std::path::Path;
+
+/// Shows a path with forward slashes, so that the outputs and the messages are the same on every
+/// platform.
+pub fn to_forward_slashes(path: &Path) -> String {
+  path.to_str().unwrap().replace('\\', "/")
+}
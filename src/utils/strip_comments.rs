@@ -0,0 +1,40 @@
+/// Replaces the comments of a GLSL source with spaces, keeping the line breaks so that line
+/// numbers do not change.
+///
+/// The glsl parser does not accept comments at the end of some preprocessor directives
+/// (e.g. `#endif // FOO_GLSL`), which is common in third-party libraries.
+pub fn strip_comments(source: &str) -> String {
+  let mut output = String::with_capacity(source.len());
+  let mut chars = source.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      ('/', Some('/')) => {
+        while let Some(next) = chars.peek() {
+          if *next == '\n' {
+            break;
+          }
+          chars.next();
+        }
+        output.push(' ');
+      }
+      ('/', Some('*')) => {
+        chars.next();
+        let mut previous = ' ';
+        for next in chars.by_ref() {
+          if next == '\n' {
+            output.push('\n');
+          }
+          if previous == '*' && next == '/' {
+            break;
+          }
+          previous = next;
+        }
+        output.push(' ');
+      }
+      _ => output.push(c),
+    }
+  }
+
+  output
+}
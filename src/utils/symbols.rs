@@ -0,0 +1,143 @@
+use glsl::syntax::{
+  Declaration, ExternalDeclaration, Identifier, Preprocessor, PreprocessorDefine, TypeName,
+  TypeSpecifierNonArray,
+};
+use glsl::visitor::{Host, Visit, Visitor};
+use std::collections::{BTreeSet, HashMap};
+
+/// Gets the names a top-level declaration defines: functions, structs, global variables,
+/// uniform blocks and macros. Overloads and prototypes define the name of their function.
+pub fn declared_symbols(declaration: &ExternalDeclaration) -> Vec<String> {
+  match declaration {
+    ExternalDeclaration::FunctionDefinition(function) => vec![function.prototype.name.to_string()],
+    ExternalDeclaration::Preprocessor(Preprocessor::Define(define)) => match define {
+      PreprocessorDefine::ObjectLike { ident, .. } => vec![ident.to_string()],
+      PreprocessorDefine::FunctionLike { ident, .. } => vec![ident.to_string()],
+    },
+    ExternalDeclaration::Preprocessor(_) => Vec::new(),
+    ExternalDeclaration::Declaration(declaration) => match declaration {
+      Declaration::FunctionPrototype(prototype) => vec![prototype.name.to_string()],
+      Declaration::InitDeclaratorList(list) => {
+        let mut names = Vec::new();
+
+        if let TypeSpecifierNonArray::Struct(struct_specifier) = &list.head.ty.ty.ty {
+          names.extend(struct_specifier.name.iter().map(|name| name.to_string()));
+        }
+
+        names.extend(list.head.name.iter().map(|name| name.to_string()));
+        names.extend(list.tail.iter().map(|tail| tail.ident.ident.to_string()));
+        names
+      }
+      Declaration::Block(block) => match &block.identifier {
+        Some(identifier) => vec![identifier.ident.to_string()],
+        // The fields of blocks without an instance name are global names
+        None => block
+          .fields
+          .iter()
+          .flat_map(|field| field.identifiers.0.iter())
+          .map(|identifier| identifier.ident.to_string())
+          .collect(),
+      },
+      Declaration::Precision(..) | Declaration::Global(..) => Vec::new(),
+    },
+  }
+}
+
+struct ReferenceVisitor {
+  references: BTreeSet<String>,
+}
+
+impl Visitor for ReferenceVisitor {
+  fn visit_identifier(&mut self, identifier: &Identifier) -> Visit {
+    self.references.insert(identifier.to_string());
+    Visit::Children
+  }
+
+  fn visit_type_name(&mut self, type_name: &TypeName) -> Visit {
+    self.references.insert(type_name.to_string());
+    Visit::Children
+  }
+}
+
+fn split_identifiers(source: &str) -> impl Iterator<Item = String> + '_ {
+  source
+    .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+    .filter(|word| !word.is_empty())
+    .map(String::from)
+}
+
+/// Gets every name used by a top-level declaration. This is a superset of the symbols it depends
+/// on, as local variables and fields are included too.
+pub fn referenced_symbols(declaration: &ExternalDeclaration) -> BTreeSet<String> {
+  let mut visitor = ReferenceVisitor {
+    references: BTreeSet::new(),
+  };
+
+  match declaration {
+    // Macros are not parsed, so their body is split into identifiers instead
+    ExternalDeclaration::Preprocessor(Preprocessor::Define(define)) => {
+      let value = match define {
+        PreprocessorDefine::ObjectLike { value, .. } => value,
+        PreprocessorDefine::FunctionLike { value, .. } => value,
+      };
+
+      visitor.references.extend(split_identifiers(value));
+    }
+    // Conditions are not parsed either, and macros only used in them are still needed
+    ExternalDeclaration::Preprocessor(Preprocessor::If(condition)) => {
+      visitor
+        .references
+        .extend(split_identifiers(&condition.condition));
+    }
+    ExternalDeclaration::Preprocessor(Preprocessor::ElIf(condition)) => {
+      visitor
+        .references
+        .extend(split_identifiers(&condition.condition));
+    }
+    _ => declaration.visit(&mut visitor),
+  }
+
+  visitor.references
+}
+
+/// Finds the top-level declarations reachable from the `roots` symbols, following the symbols
+/// every reachable declaration references. Declarations that do not define any symbol
+/// (e.g. `precision` statements or preprocessor directives) are always kept, as well as the ones
+/// for which `keep` returns true.
+pub fn reachable_declarations(
+  declarations: &[ExternalDeclaration],
+  roots: &[String],
+  keep: impl Fn(&ExternalDeclaration) -> bool,
+) -> Vec<bool> {
+  let mut owners: HashMap<String, Vec<usize>> = HashMap::new();
+  let mut reachable = vec![false; declarations.len()];
+  let mut pending = roots.to_vec();
+
+  for (i, declaration) in declarations.iter().enumerate() {
+    let names = declared_symbols(declaration);
+
+    if names.is_empty() || keep(declaration) {
+      reachable[i] = true;
+      pending.extend(referenced_symbols(declaration));
+    }
+
+    for name in names {
+      owners.entry(name).or_default().push(i);
+    }
+  }
+
+  let mut visited = BTreeSet::new();
+
+  while let Some(name) = pending.pop() {
+    if !visited.insert(name.clone()) {
+      continue;
+    }
+
+    for i in owners.get(&name).into_iter().flatten() {
+      reachable[*i] = true;
+      pending.extend(referenced_symbols(&declarations[*i]));
+    }
+  }
+
+  reachable
+}
@@ -0,0 +1,19 @@
+use glsl::syntax::{Declaration, ExternalDeclaration};
+use glsl::transpiler::glsl as transpiler;
+
+/// Emits GLSL for a top-level declaration with the glsl transpiler.
+///
+/// The transpiler forgets the `precision` keyword of precision statements, so they are written by
+/// hand.
+pub fn show_external_declaration(output: &mut String, declaration: &ExternalDeclaration) {
+  match declaration {
+    ExternalDeclaration::Declaration(Declaration::Precision(qualifier, ty)) => {
+      output.push_str("precision ");
+      transpiler::show_precision_qualifier(output, qualifier);
+      output.push(' ');
+      transpiler::show_type_specifier(output, ty);
+      output.push_str(";\n");
+    }
+    _ => transpiler::show_external_declaration(output, declaration),
+  }
+}
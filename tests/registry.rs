@@ -0,0 +1,105 @@
+use glsl_types::config::Settings;
+use glsl_types::generator::common::{self, ProcessedShader};
+use glsl_types::generator::{Generator, OutputFile, Reflection, Registry};
+use glsl_types::ShaderType;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// A generator written outside of the crate, which lists the uniforms of a shader.
+struct UniformList;
+
+impl Generator for UniformList {
+  fn name(&self) -> &str {
+    "uniforms"
+  }
+
+  fn generate(
+    &self,
+    reflection: &Reflection,
+    _settings: &Settings,
+  ) -> Result<Vec<OutputFile>, String> {
+    let uniforms = reflection
+      .data
+      .uniforms
+      .iter()
+      .map(|uniform| uniform.identifier.as_str())
+      .collect::<Vec<&str>>();
+
+    Ok(vec![OutputFile {
+      path: PathBuf::from(format!("{}.txt", reflection.name)),
+      contents: uniforms.join("\n"),
+    }])
+  }
+}
+
+fn reflection(source: &str, stage: ShaderType) -> Reflection {
+  let content = source.to_string();
+
+  Reflection {
+    name: "blur_frag".to_string(),
+    export_name: "BlurFrag".to_string(),
+    stage,
+    data: common::extract_shader_data(&content, stage),
+    shader: ProcessedShader {
+      defines: Vec::new(),
+      includes: Vec::new(),
+      source: content.clone(),
+      content,
+      names: BTreeMap::new(),
+    },
+    variants: Vec::new(),
+  }
+}
+
+#[test]
+fn registers_an_external_generator() {
+  let mut registry = Registry::default();
+  registry.register(Box::new(UniformList));
+
+  assert!(registry.names().contains(&"uniforms"));
+  assert!(registry.names().contains(&"typescript"));
+
+  let reflection = reflection(
+    "#version 300 es\nprecision highp float;\nuniform vec4 uColor;\nuniform float uRadius;\nout vec4 color;\nvoid main() { color = uColor * uRadius; }\n",
+    ShaderType::Fragment,
+  );
+  let files = registry
+    .get("uniforms")
+    .unwrap()
+    .generate(&reflection, &Settings::default())
+    .unwrap();
+
+  assert_eq!(files.len(), 1);
+  assert_eq!(files[0].path, PathBuf::from("blur_frag.txt"));
+  assert_eq!(files[0].contents, "uColor\nuRadius");
+}
+
+#[test]
+fn replaces_the_generator_with_the_same_name() {
+  struct Empty;
+
+  impl Generator for Empty {
+    fn name(&self) -> &str {
+      "typescript"
+    }
+
+    fn generate(&self, _: &Reflection, _: &Settings) -> Result<Vec<OutputFile>, String> {
+      Ok(Vec::new())
+    }
+  }
+
+  let mut registry = Registry::default();
+  let count = registry.names().len();
+  registry.register(Box::new(Empty));
+
+  assert_eq!(registry.names().len(), count);
+
+  let reflection = reflection("#version 300 es\nvoid main() {}\n", ShaderType::Fragment);
+  let files = registry
+    .get("typescript")
+    .unwrap()
+    .generate(&reflection, &Settings::default())
+    .unwrap();
+
+  assert!(files.is_empty());
+}